@@ -1,25 +1,45 @@
 // demonstrates advanced raytracing
 // see: https://medium.com/@cadenmarinozzi/simulating-a-schwarzschild-black-hole-part-1-the-background-and-raytracer-7de436a56b7e
 // space bg image credit: https://svs.gsfc.nasa.gov/4851/
-use elara_gfx::{gl_info, Buffer, BufferType, Program, Shader, VertexArray, Texture2D, PixelArray, Uniform};
-use elara_gfx::{GLWindow, HandlerResult, WindowHandler};
+use elara_gfx::{gl_info, impl_std140, PrimitiveMode, Buffer, BufferType, ProgramCache, ReloadableProgram, ShaderStage, StageSource, VertexArray, Texture2D, PixelArray, SamplerDesc, UniformBuffer, Wrap};
+use elara_gfx::{GLWindow, GfxResult, HandlerResult, WindowHandler, WindowOptions};
 use elara_log::prelude::*;
 use std::error::Error;
 
 const VERT_SHADER: &str = include_str!("shaders/blackhole.vert");
-const FRAG_SHADER: &str = include_str!("shaders/blackhole.frag");
+// Loaded at runtime and reloaded when saved
+const FRAG_SHADER_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/shaders/blackhole.frag");
+// Compiled programs are kept here between runs
+const SHADER_CACHE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/shader-cache");
 const IMG_PATH: &str = "resources/starmap_g4k.jpg";
+// Binding point of the `Scene` uniform block
+const SCENE_BINDING: u32 = 0;
+
+// Matches the `Scene` block in blackhole.frag
+#[derive(Debug, Clone, Copy)]
+struct SceneParams {
+    resolution: [f32; 2],
+    cam_pos: [f32; 3],
+    blackhole_pos: [f32; 3],
+}
+impl_std140!(SceneParams { resolution, cam_pos, blackhole_pos });
 
 struct Handler {
     vao: VertexArray,
+    _vbo: Buffer<f32>,
     texture: Texture2D,
-    resolution: (f32, f32),
-    program: Program
+    scene: SceneParams,
+    scene_ubo: UniformBuffer<SceneParams>,
+    program: ReloadableProgram
 }
 
 impl Handler {
-    fn new(win: &GLWindow) -> Result<Handler, String> {
-    	let resolution = (win.width() as f32, win.height() as f32);
+    fn new(win: &GLWindow) -> GfxResult<Handler> {
+        let scene = SceneParams {
+            resolution: [win.width() as f32, win.height() as f32],
+            cam_pos: [0.0, 0.0, -10.0],
+            blackhole_pos: [0.0, 0.0, 0.0],
+        };
         let vertices: [f32; 24] = [
              // positions // texture coords
              1.0,  1.0,   1.0, 1.0, // top right
@@ -33,13 +53,14 @@ impl Handler {
         let vao = VertexArray::new()?;
         vao.bind();
 
-        let vbo = Buffer::new()?;
+        let mut vbo = Buffer::new()?;
         vbo.bind(BufferType::Array);
-        vbo.data::<f32>(BufferType::Array, &vertices, gl::STATIC_DRAW);
+        vbo.data(BufferType::Array, &vertices, gl::STATIC_DRAW);
         
-        let vertex_shader = Shader::new(&VERT_SHADER, gl::VERTEX_SHADER)?;
-        let fragment_shader = Shader::new(&FRAG_SHADER, gl::FRAGMENT_SHADER)?;
-        let program = Program::new(&[vertex_shader, fragment_shader])?;
+        let program = ReloadableProgram::with_cache(vec![
+            (ShaderStage::Vertex, StageSource::embedded("blackhole.vert", VERT_SHADER)),
+            (ShaderStage::Fragment, StageSource::file(FRAG_SHADER_PATH)),
+        ], ProgramCache::new(SHADER_CACHE_DIR))?;
         program.use_program();
         
         let pos_attrib = vao.get_attrib_location(&program, "position");
@@ -53,39 +74,42 @@ impl Handler {
         let texture = Texture2D::new()?;
         texture.bind();
         
-        texture.parameter_2d(gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
-        texture.parameter_2d(gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
-        texture.parameter_2d(gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-        texture.parameter_2d(gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        texture.set_sampler(&SamplerDesc::linear().wrap(Wrap::Repeat));
 
         let mut img = PixelArray::load_jpg_from_path(IMG_PATH).unwrap();
         img.flipv();
         texture.set_image_2d(img);
         texture.generate_mipmap();
-        Ok(Handler { vao, texture, resolution, program })
+
+        let scene_ubo = UniformBuffer::new(&scene)?;
+        program.bind_uniform_buffer("Scene", &scene_ubo, SCENE_BINDING)?;
+        Ok(Handler { vao, _vbo: vbo, texture, scene, scene_ubo, program })
     }
 }
 
 impl WindowHandler for Handler {
-    fn on_draw(&mut self) -> Result<(), String> {
+    fn on_resize(&mut self, width: i32, height: i32) -> HandlerResult<()> {
+        self.scene.resolution = [width as f32, height as f32];
+        self.scene_ubo.set(&self.scene)?;
+        Ok(())
+    }
+
+    fn on_draw(&mut self) -> HandlerResult<()> {
         // All drawing code should be put here
+        self.program.poll();
         unsafe {
-            let res_uniform = Uniform::new(&self.program, "u_resolution")?;
-            res_uniform.uniform2f(self.resolution.0, self.resolution.1);
-                        
             gl::Clear(gl::COLOR_BUFFER_BIT);
             self.texture.bind();
-            self.vao.bind();
-            gl::DrawArrays(gl::TRIANGLES, 0, 6);
+            self.vao.draw_arrays(PrimitiveMode::Triangles, 0, 6);
             self.vao.unbind();
             self.texture.unbind();
         }
         Ok(())
     }
 
-    fn post_draw(&mut self) -> Result<(), String> {
+    fn post_draw(&mut self) -> HandlerResult<()> {
     	unsafe {
-    		let img = self.save_rendering(self.resolution.0 as i32, self.resolution.1 as i32).unwrap();
+    		let img = self.save_rendering(self.scene.resolution[0] as i32, self.scene.resolution[1] as i32).unwrap();
             img.save_as_ppm("black_hole_render.ppm");
     	}
     	Ok(())
@@ -96,13 +120,18 @@ fn main() -> Result<(), Box<dyn Error>> {
     Logger::new().init().unwrap();
     info!("Starting logging...");
 
-    let (app, window) = GLWindow::new_with_title("Black hole raytracer")?;
+    // MSAA is wasted on a single fullscreen quad
+    let opts = WindowOptions::default()
+        .title("Black hole raytracer")
+        .gl_version(3, 3)
+        .samples(None);
+    let (app, window) = GLWindow::new(opts)?;
     window.get_context()?;
     gl_info();
     
     let render_handler = Handler::new(&window)?;
 
     // Event handling
-    app.run_loop(window, render_handler);
+    app.run_loop(window, render_handler)?;
     Ok(())
 }
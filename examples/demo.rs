@@ -1,12 +1,12 @@
 use elara_gfx::gl_info;
-use elara_gfx::{GLWindow, WindowHandler};
+use elara_gfx::{GLWindow, HandlerResult, WindowHandler};
 use elara_log::prelude::*;
 use std::error::Error;
 
 struct Handler;
 
 impl WindowHandler for Handler {
-    fn on_draw(&mut self) -> Result<(), String> {
+    fn on_draw(&mut self) -> HandlerResult<()> {
         // All drawing code should be put here
         unsafe {
             gl::ClearColor(0.1, 0.1, 0.1, 1.0);
@@ -25,6 +25,6 @@ fn main() -> Result<(), Box<dyn Error>> {
     gl_info();
 
     // Event handling
-    app.run_loop(window, Handler);
+    app.run_loop(window, Handler)?;
     Ok(())
 }
@@ -1,41 +1,45 @@
 // Takes almost any fragment shader as input and renders with the shader
 // should (eventually) run almost any shader on Shadertoy
-use elara_gfx::{gl_info, Buffer, BufferType, Program, Shader, Uniform, VertexArray};
-use elara_gfx::{GLWindow, HandlerResult, WindowHandler};
+use elara_gfx::{gl_info, PrimitiveMode, Buffer, BufferType, ProgramCache, ReloadableProgram, ShaderStage, StageSource, VertexArray};
+use elara_gfx::{GLWindow, GfxResult, HandlerResult, WindowHandler, Key, KeyEvent, FrameTiming, RedrawMode};
 use elara_log::prelude::*;
 use std::error::Error;
-use std::fs::read_to_string;
+use std::path::Path;
 use std::process::exit;
-use std::time::{Duration, Instant};
 
 const DUMMY_VERTEX_SHADER: &'static str = include_str!("shaders/quad.vert");
+// Compiled programs are kept here between runs
+const SHADER_CACHE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/shader-cache");
 
 struct Handler {
     vao: VertexArray,
-    program: Program,
+    _vbo: Buffer<f32>,
+    ebo: Buffer<u32>,
+    // Recompiled whenever the shader file is saved
+    program: ReloadableProgram,
     resolution: (f32, f32),
-    frame_count: u32,
-    start_time: Instant,
+    mouse: (f32, f32),
+    elapsed_time: f32,
+    save_requested: bool,
 }
 
-fn show_render_stats(frame_interval: u32, current_frame: u32, frame_render_time: Duration) {
+fn show_render_stats(frame_interval: u64, timing: &FrameTiming) {
     // Shows render stats once per <frame_interval> frames
-    if current_frame % frame_interval == 0 {
+    if timing.frame_index() % frame_interval == 0 {
         debug!(
-            "Frame {} average render time {:?}",
-            current_frame, frame_render_time
+            "Frame {} running at {:.1} FPS",
+            timing.frame_index(), timing.fps()
         );
     }
 }
 
 impl Handler {
-    fn new(win: &GLWindow, shader_src: String) -> Result<Handler, String> {
-        let start_time = Instant::now();
-        let frame_count = 0;
+    fn new(win: &GLWindow, shader_path: &Path) -> GfxResult<Handler> {
         let resolution = (win.width() as f32, win.height() as f32);
-        let vertex_shader = Shader::new(&DUMMY_VERTEX_SHADER, gl::VERTEX_SHADER)?;
-        let fragment_shader = Shader::new(&shader_src, gl::FRAGMENT_SHADER)?;
-        let program = Program::new(&[vertex_shader, fragment_shader])?;
+        let program = ReloadableProgram::with_cache(vec![
+            (ShaderStage::Vertex, StageSource::embedded("quad.vert", DUMMY_VERTEX_SHADER)),
+            (ShaderStage::Fragment, StageSource::file(shader_path)),
+        ], ProgramCache::new(SHADER_CACHE_DIR))?;
         program.use_program();
 
         // Render 1 fullscreen quad for shaders
@@ -49,19 +53,18 @@ impl Handler {
 
         #[rustfmt::skip]
         let indices = [
-            0, 1, 3,
+            0_u32, 1, 3,
             1, 2, 3
         ];
 
         let vao = VertexArray::new()?;
-        let vbo = Buffer::new()?;
-        let ebo = Buffer::new()?;
+        let mut vbo = Buffer::new()?;
+        let mut ebo = Buffer::new()?;
 
         vao.bind();
         vbo.bind(BufferType::Array);
-        vbo.data::<f32>(BufferType::Array, &vertices, gl::STATIC_DRAW);
-        ebo.bind(BufferType::ElementArray);
-        ebo.data::<i32>(BufferType::ElementArray, &indices, gl::STATIC_DRAW);
+        vbo.data(BufferType::Array, &vertices, gl::STATIC_DRAW);
+        ebo.data(BufferType::ElementArray, &indices, gl::STATIC_DRAW);
         vao.vertex_attrib_pointer::<f32>(0, 3, gl::FLOAT, false, 0, 0);
         vao.enable_vertex_attrib(0);
         vbo.unbind(BufferType::Array);
@@ -69,55 +72,75 @@ impl Handler {
 
         Ok(Handler {
             vao,
+            _vbo: vbo,
+            ebo,
             program,
             resolution,
-            frame_count,
-            start_time,
+            mouse: (0.0, 0.0),
+            elapsed_time: 0.0,
+            save_requested: false,
         })
     }
-    fn current_frame(&self) -> u32 {
-        self.frame_count
-    }
-    fn add_frame(&mut self) {
-        self.frame_count += 1
-    }
-    fn get_elapsed_time(&self) -> Duration {
-        self.start_time.elapsed()
-    }
 }
 
 impl WindowHandler for Handler {
+    fn on_update(&mut self, _dt: f32, timing: &FrameTiming) -> HandlerResult<()> {
+        self.elapsed_time = timing.elapsed().as_secs_f32();
+        // Avoid showing render stats too often so we show only once
+        // per every few hundred frames
+        show_render_stats(500, timing);
+        Ok(())
+    }
+
     fn on_draw(&mut self) -> HandlerResult<()> {
+        self.program.poll();
+        self.program.set("u_time", self.elapsed_time)?;
+        self.program.set("u_resolution", [self.resolution.0, self.resolution.1])?;
+        self.program.set("u_mouse", [self.mouse.0, self.mouse.1])?;
         unsafe {
-            let res_uniform = Uniform::new(&self.program, "u_resolution")?;
-            let time_uniform = Uniform::new(&self.program, "u_time")?;
-
-            let now = std::time::Instant::now();
-            let elapsed_time = now.duration_since(self.start_time).as_secs_f32();
-            
-            time_uniform.uniform1f(elapsed_time);
-            res_uniform.uniform2f(self.resolution.0, self.resolution.1);
-
             gl::ClearColor(0.2, 0.3, 0.3, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
-            self.vao.bind();
-            gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, std::ptr::null());
-            self.vao.unbind();
         }
-        self.add_frame();
-        let current_frame = self.current_frame();
-        let frame_render_time = self.get_elapsed_time() / current_frame;
-        // Avoid showing render time counter too often so we show only once
-        // per every few hundred frames
-        show_render_stats(500, current_frame, frame_render_time);
+        self.vao.draw_elements(PrimitiveMode::Triangles, &self.ebo, 0, 6)?;
+        self.vao.unbind();
+        Ok(())
+    }
+
+    fn on_resize(&mut self, width: i32, height: i32) -> HandlerResult<()> {
+        self.resolution = (width as f32, height as f32);
+        Ok(())
+    }
+
+    fn on_mouse_move(&mut self, x: f64, y: f64) -> HandlerResult<()> {
+        // Shaders expect the origin at the bottom left
+        self.mouse = (x as f32, self.resolution.1 - y as f32);
         Ok(())
     }
 
-    fn post_draw(&mut self) -> Result<(), String> {
-    	unsafe {
-    		let img = self.save_rendering(self.resolution.0 as i32, self.resolution.1 as i32).unwrap();
+    fn on_key(&mut self, event: KeyEvent) -> HandlerResult<()> {
+        if !event.state.is_pressed() {
+            return Ok(());
+        }
+        match event.key {
+            Some(Key::Escape) => {
+                info!("Escape pressed, exiting...");
+                exit(0);
+            }
+            // Saving every frame is far too slow when
+            // rendering continuously, so only save on request
+            Some(Key::S) => self.save_requested = true,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn post_draw(&mut self) -> HandlerResult<()> {
+        if self.save_requested {
+            let img = self.save_rendering(self.resolution.0 as i32, self.resolution.1 as i32).unwrap();
             img.save_as_ppm("render.ppm").unwrap();
-    	}
+            info!("Saved rendering to render.ppm");
+            self.save_requested = false;
+        }
     	Ok(())
     }
 }
@@ -132,21 +155,21 @@ fn main() -> Result<(), Box<dyn Error>> {
         error!("Usage: fragshader input.frag");
         exit(1);
     }
-    let shader_path = &args[0];
+    let shader_path = Path::new(&args[0]);
 
-    info!("Attempting to load shader {}", &shader_path);
-    let shader_src: String = read_to_string(&shader_path)?.parse()?;
+    info!("Attempting to load shader {}", shader_path.display());
 
-    let (app, window) = GLWindow::new_with_title("OpenGL shaders")?;
+    let (mut app, window) = GLWindow::new_with_title("OpenGL shaders")?;
+    app.set_redraw_mode(RedrawMode::Continuous)?;
     window.get_context()?;
     gl_info();
 
     // Run all OpenGL calls that only
     // needs to be run once in advance
     // of rendering to improve performance
-    let render_handler = Handler::new(&window, shader_src)?;
+    let render_handler = Handler::new(&window, shader_path)?;
 
     // Event handling
-    app.run_loop(window, render_handler);
+    app.run_loop(window, render_handler)?;
     Ok(())
 }
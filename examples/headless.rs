@@ -0,0 +1,33 @@
+// Renders shapes without opening a window, useful
+// for CI machines and batch rendering on servers
+use elara_gfx::{gl_info, Draw, GfxResult, GLSurface, HeadlessContext};
+use elara_gfx::canvas::{Canvas, CanvasHandler, Color};
+use elara_log::prelude::*;
+use std::error::Error;
+
+struct CanvasContext;
+
+impl Draw for CanvasContext {
+    fn draw(&mut self, win: &dyn GLSurface) -> GfxResult<Canvas> {
+        let mut canvas = Canvas::new(win);
+        canvas.set_background(Color(255, 255, 255, 1.0));
+        canvas.add_rect(-0.5, 0.0, 0.8, 0.5, Color(255, 0, 0, 1.0));
+        canvas.add_polygon(0.0, 0.0, 0.3, 6, Color(255, 0, 255, 1.0));
+        canvas.add_circle(0.0, -0.2, 0.2, Color(0, 255, 255, 1.0));
+        Ok(canvas)
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    Logger::new().init().unwrap();
+    info!("Starting logging...");
+
+    let mut context = HeadlessContext::new(800, 600)?;
+    gl_info();
+
+    let mut render_handler = CanvasHandler::new(&context, CanvasContext, false)?;
+    let img = context.render_frame(&mut render_handler)?;
+    img.save_as_ppm("headless.ppm")?;
+    info!("Saved rendering to headless.ppm");
+    Ok(())
+}
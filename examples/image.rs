@@ -1,6 +1,6 @@
 // demonstrates how to draw a basic image
-use elara_gfx::{gl_info, Buffer, BufferType, Program, Shader, VertexArray, Texture2D, PixelArray};
-use elara_gfx::{GLWindow, HandlerResult, WindowHandler};
+use elara_gfx::{gl_info, PrimitiveMode, Buffer, BufferType, Program, Shader, ShaderStage, VertexArray, Texture2D, PixelArray, SamplerDesc, Wrap, enable_alpha_blend};
+use elara_gfx::{GLWindow, GfxResult, HandlerResult, WindowHandler};
 use elara_log::prelude::*;
 use std::error::Error;
 
@@ -10,11 +10,13 @@ const IMG_PATH: &str = "resources/text.png";
 
 struct Handler {
     vao: VertexArray,
-    texture: Texture2D
+    _vbo: Buffer<f32>,
+    texture: Texture2D,
+    _program: Program
 }
 
 impl Handler {
-    fn new() -> Result<Handler, String> {
+    fn new() -> GfxResult<Handler> {
         let vertices: [f32; 24] = [
              // positions // texture coords
              0.5,  0.5,   1.0, 1.0, // top right
@@ -28,12 +30,12 @@ impl Handler {
         let vao = VertexArray::new()?;
         vao.bind();
 
-        let vbo = Buffer::new()?;
+        let mut vbo = Buffer::new()?;
         vbo.bind(BufferType::Array);
-        vbo.data::<f32>(BufferType::Array, &vertices, gl::STATIC_DRAW);
+        vbo.data(BufferType::Array, &vertices, gl::STATIC_DRAW);
         
-        let vertex_shader = Shader::new(&VERT_SHADER, gl::VERTEX_SHADER)?;
-        let fragment_shader = Shader::new(&FRAG_SHADER, gl::FRAGMENT_SHADER)?;
+        let vertex_shader = Shader::new(&VERT_SHADER, ShaderStage::Vertex)?;
+        let fragment_shader = Shader::new(&FRAG_SHADER, ShaderStage::Fragment)?;
         let program = Program::new(&[vertex_shader, fragment_shader])?;
         program.use_program();
         
@@ -48,30 +50,25 @@ impl Handler {
         let texture = Texture2D::new()?;
         texture.bind();
         
-        texture.parameter_2d(gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
-        texture.parameter_2d(gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
-        texture.parameter_2d(gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-        texture.parameter_2d(gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-        
-        texture.enable_alpha_blend();
+        texture.set_sampler(&SamplerDesc::linear().wrap(Wrap::Repeat));
+        enable_alpha_blend();
 
         let mut img = PixelArray::load_png_from_path(IMG_PATH).unwrap();
         img.flipv();
         texture.set_image_2d(img);
         texture.generate_mipmap();
-        Ok(Handler { vao, texture })
+        Ok(Handler { vao, _vbo: vbo, texture, _program: program })
     }
 }
 
 impl WindowHandler for Handler {
-    fn on_draw(&mut self) -> Result<(), String> {
+    fn on_draw(&mut self) -> HandlerResult<()> {
         // All drawing code should be put here
         unsafe {
             gl::ClearColor(1.0, 1.0, 1.0, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
             self.texture.bind();
-            self.vao.bind();
-            gl::DrawArrays(gl::TRIANGLES, 0, 6);
+            self.vao.draw_arrays(PrimitiveMode::Triangles, 0, 6);
             self.vao.unbind();
             self.texture.unbind();
         }
@@ -90,6 +87,6 @@ fn main() -> Result<(), Box<dyn Error>> {
     let render_handler = Handler::new()?;
 
     // Event handling
-    app.run_loop(window, render_handler);
+    app.run_loop(window, render_handler)?;
     Ok(())
 }
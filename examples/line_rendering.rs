@@ -1,4 +1,4 @@
-use elara_gfx::{gl_info, WindowHandler, GLWindow};
+use elara_gfx::{gl_info, WindowHandler, GfxResult, HandlerResult, GLWindow};
 use elara_gfx::canvas::{LineRenderer, Color};
 use std::error::Error;
 use elara_log::prelude::*;
@@ -8,14 +8,14 @@ struct Handler {
 }
 
 impl Handler {
-    fn new() -> Result<Handler, String> {
+    fn new() -> GfxResult<Handler> {
         let renderer = LineRenderer::new()?;
         Ok(Handler{ renderer })
     }
 }
 
 impl WindowHandler for Handler {
-    fn on_draw(&mut self) -> Result<(), String> {
+    fn on_draw(&mut self) -> HandlerResult<()> {
         unsafe {
             gl::ClearColor(0.2, 0.3, 0.3, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
@@ -37,6 +37,6 @@ fn main() -> Result<(), Box<dyn Error>> {
     let render_handler = Handler::new()?;
 
     // Event handling
-    app.run_loop(window, render_handler);
+    app.run_loop(window, render_handler)?;
     Ok(())
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 // This demo shows how to draw polygons and other shapes
 // using elara-gfx
-use elara_gfx::{gl_info, Draw, GLWindow};
+use elara_gfx::{gl_info, Draw, GfxResult, GLSurface, GLWindow};
 use elara_gfx::canvas::{Canvas, CanvasHandler, Color};
 use elara_log::prelude::*;
 use std::error::Error;
@@ -14,8 +14,8 @@ impl CanvasContext {
 }
 
 impl Draw for CanvasContext {
-    fn draw(&mut self, win: &GLWindow) -> Result<Canvas, String> {
-        let mut canvas = Canvas::new(&win);
+    fn draw(&mut self, win: &dyn GLSurface) -> GfxResult<Canvas> {
+        let mut canvas = Canvas::new(win);
         canvas.set_background(Color(255, 255, 255, 1.0));
         canvas.add_rect(-0.5, 0.0, 0.8, 0.5, Color(255, 0, 0, 1.0));
         canvas.add_polygon(0.0, 0.0, 0.3, 6, Color(255, 0, 255, 1.0));
@@ -44,6 +44,6 @@ fn main() -> Result<(), Box<dyn Error>> {
     let render_handler = CanvasHandler::new(&window, canvas, false)?;
 
     // Event handling
-    app.run_loop(window, render_handler);
+    app.run_loop(window, render_handler)?;
     Ok(())
 }
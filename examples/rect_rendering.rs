@@ -1,4 +1,4 @@
-use elara_gfx::{gl_info, WindowHandler, HandlerResult, GLWindow};
+use elara_gfx::{gl_info, WindowHandler, GfxResult, HandlerResult, GLWindow};
 use std::error::Error;
 use elara_log::prelude::*;
 use elara_gfx::canvas::{RectRenderer, RectStyle};
@@ -8,7 +8,7 @@ struct Handler {
 }
 
 impl Handler {
-    fn new() -> Result<Handler, String> {
+    fn new() -> GfxResult<Handler> {
         let renderer = RectRenderer::new()?;
         Ok(Handler { renderer })
     }
@@ -37,6 +37,6 @@ fn main() -> Result<(), Box<dyn Error>> {
     let render_handler = Handler::new()?;
 
     // Event handling
-    app.run_loop(window, render_handler);
+    app.run_loop(window, render_handler)?;
     Ok(())
 }
\ No newline at end of file
@@ -6,13 +6,15 @@
 in vec2 TexCoord;
 out vec4 fragColor;
 
-uniform vec2 u_resolution;
+// Scene parameters, shared through a uniform buffer
+layout(std140) uniform Scene {
+	vec2 u_resolution;
+	vec3 camPos;
+	vec3 blackholePos;
+};
 // texture sampler
 uniform sampler2D uSpaceTexture;
 
-vec3 camPos = vec3(0, 0, -10);
-vec3 blackholePos = vec3(0, 0, 0);
-
 vec4 raytrace(vec3 rayDir, vec3 rayPos) {
 	float h2 = pow(length(cross(rayPos, rayDir)), 2.0);
 
@@ -28,8 +30,6 @@ vec4 raytrace(vec3 rayDir, vec3 rayPos) {
 void main()
 {
 	vec2 uv = (TexCoord - 0.5) * 2.0 * vec2(u_resolution.x / u_resolution.y, 1);
-	vec3 camPos = vec3(0, 0, -10);
-	vec3 blackholePos = vec3(0, 0, 0);
 	vec3 rayDir = normalize(vec3(uv, 1));
 	vec3 rayPos = camPos;
 	fragColor = raytrace(rayDir, rayPos);
@@ -5,12 +5,11 @@ uniform float u_time;
 uniform vec2 u_resolution;
 out vec4 frag_color;
 
-#define PI 3.14159
-#define	TAU 6.28318
+#include "common.glsl"
 
 void main()
 {
-	vec2 p = (2.0*gl_FragCoord.xy-u_resolution.xy)/u_resolution.y;
+	vec2 p = centered_uv(gl_FragCoord.xy, u_resolution);
     float a = atan(p.x,p.y);
     float r = length(p)*0.75;
     vec2 uv = vec2(a/TAU,r);
@@ -0,0 +1,18 @@
+// Helpers shared by the example shaders, fragshader
+// expands `#include "common.glsl"` before compiling
+#pragma once
+
+#define PI 3.14159
+#define TAU 6.28318
+
+// Pixel coordinates with the origin at the center of the
+// screen and y going from -1 to 1
+vec2 centered_uv(vec2 frag_coord, vec2 resolution) {
+	return (2.0 * frag_coord - resolution) / resolution.y;
+}
+
+// Pixel coordinates with y going from 0 to 1 and x scaled
+// by the aspect ratio
+vec2 aspect_uv(vec2 frag_coord, vec2 resolution) {
+	return frag_coord / resolution.y;
+}
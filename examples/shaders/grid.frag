@@ -4,8 +4,10 @@
 uniform vec2 u_resolution;
 out vec4 fragColor;
 
+#include "common.glsl"
+
 void main() {
-    vec2 uv = gl_FragCoord.xy / u_resolution.xy * vec2(u_resolution.x / u_resolution.y, 1.0);
+    vec2 uv = aspect_uv(gl_FragCoord.xy, u_resolution);
     // get some diagonal lines going
     uv.yx += uv.xy * 0.1;
 
@@ -17,9 +17,8 @@ out vec4 fragColor;
 #define HEIGHT 12.
 
 vec3 _col;
-    
-#define PI 3.14159
-#define	TAU 6.28318
+
+#include "common.glsl"
 
 
 vec3 spunk(vec2 uv)
@@ -1,6 +1,6 @@
 // This demo showcases how to render a texture on top of a shape in elara-gfx
-use elara_gfx::{gl_info, Buffer, BufferType, Program, Shader, VertexArray, PixelArray, Uniform, Texture2D};
-use elara_gfx::{GLWindow, HandlerResult, WindowHandler};
+use elara_gfx::{gl_info, PrimitiveMode, Buffer, BufferType, Program, Shader, ShaderStage, VertexArray, PixelArray, Texture2D, SamplerDesc, Wrap, enable_alpha_blend};
+use elara_gfx::{GLWindow, GfxResult, HandlerResult, WindowHandler};
 use elara_log::prelude::*;
 use std::error::Error;
 
@@ -12,11 +12,12 @@ struct Handler {
     aspect_ratio: f32,
     num_vertices: f32,
     vao: VertexArray,
+    _vbo: Buffer<f32>,
     texture: Texture2D,
     program: Program
 }
 impl Handler {
-    fn new(win: &GLWindow) -> Result<Handler, String> {
+    fn new(win: &GLWindow) -> GfxResult<Handler> {
 
         let aspect_ratio = win.height() as f32 / win.width() as f32;
         
@@ -52,9 +53,8 @@ impl Handler {
         let texture = Texture2D::new()?;
         texture.bind();
 
-        texture.parameter_2d(gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-        texture.parameter_2d(gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-        texture.enable_alpha_blend();
+        texture.set_sampler(&SamplerDesc::nearest().wrap(Wrap::Repeat));
+        enable_alpha_blend();
 
         let mut img = PixelArray::load_png_from_path(IMG_PATH).unwrap();
         img.flipv();
@@ -64,12 +64,12 @@ impl Handler {
         let vao = VertexArray::new()?;
         vao.bind();
 
-        let vbo = Buffer::new()?;
+        let mut vbo = Buffer::new()?;
         vbo.bind(BufferType::Array);
-        vbo.data::<f32>(BufferType::Array, &vertices, gl::STATIC_DRAW);
+        vbo.data(BufferType::Array, &vertices, gl::STATIC_DRAW);
 
-        let vertex_shader = Shader::new(&VERT_SHADER, gl::VERTEX_SHADER)?;
-        let fragment_shader = Shader::new(&FRAG_SHADER, gl::FRAGMENT_SHADER)?;
+        let vertex_shader = Shader::new(&VERT_SHADER, ShaderStage::Vertex)?;
+        let fragment_shader = Shader::new(&FRAG_SHADER, ShaderStage::Fragment)?;
         let program = Program::new(&[vertex_shader, fragment_shader])?;
         program.use_program();
 
@@ -85,7 +85,7 @@ impl Handler {
         vao.vertex_attrib_pointer::<f32>(col_attrib as u32, 4, gl::FLOAT, false, 8, 2);
         vao.vertex_attrib_pointer::<f32>(tex_coord_attrib as u32, 2, gl::FLOAT, false, 8, 6);
 
-        Ok(Handler {aspect_ratio, num_vertices, vao, texture, program})
+        Ok(Handler {aspect_ratio, num_vertices, vao, _vbo: vbo, texture, program})
 
     }
 }
@@ -93,13 +93,11 @@ impl Handler {
 impl WindowHandler for Handler {
     fn on_draw(&mut self) -> HandlerResult<()> {
         unsafe {
-            let aspect_ratio_uniform = Uniform::new(&self.program, "aspect_ratio")?;
-            aspect_ratio_uniform.uniform1f(self.aspect_ratio);
+            self.program.set("aspect_ratio", self.aspect_ratio)?;
             gl::ClearColor(1.0, 1.0, 1.0, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
             self.texture.bind();
-            self.vao.bind();
-            gl::DrawArrays(gl::TRIANGLES, 0, self.num_vertices as i32);
+            self.vao.draw_arrays(PrimitiveMode::Triangles, 0, self.num_vertices as usize);
             self.vao.unbind();
             self.texture.unbind();
         }
@@ -118,7 +116,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let render_handler = Handler::new(&window)?;
 
     // Event handling
-    app.run_loop(window, render_handler);
+    app.run_loop(window, render_handler)?;
     Ok(())
 }
 
@@ -1,6 +1,6 @@
 // This demo showcases how to render a texture on top of a shape in elara-gfx
-use elara_gfx::{gl_info, Buffer, BufferType, Program, Shader, VertexArray, PixelArray, Uniform, Texture2D};
-use elara_gfx::{GLWindow, HandlerResult, WindowHandler};
+use elara_gfx::{gl_info, PrimitiveMode, impl_vertex_layout, Buffer, BufferType, Program, Shader, ShaderStage, VertexArray, PixelArray, Texture2D, SamplerDesc, Wrap};
+use elara_gfx::{GLWindow, GfxResult, HandlerResult, WindowHandler};
 use elara_log::prelude::*;
 use std::error::Error;
 
@@ -8,14 +8,26 @@ const VERT_SHADER: &str = include_str!("shaders/tex_and_shape.vert");
 const FRAG_SHADER: &str = include_str!("shaders/tex_and_shape.frag");
 const IMG_PATH: &str = "resources/text_white.png";
 
+// Field names match the attributes in tex_and_shape.vert
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Vertex {
+    position: [f32; 2],
+    vertex_color: [f32; 3],
+    tex_coord: [f32; 2],
+}
+
+impl_vertex_layout!(Vertex { position, vertex_color, tex_coord });
+
 struct Handler {
     aspect_ratio: f32,
     vao: VertexArray,
+    _vbo: Buffer<f32>,
     texture: Texture2D,
     program: Program
 }
 impl Handler {
-    fn new(win: &GLWindow) -> Result<Handler, String> {
+    fn new(win: &GLWindow) -> GfxResult<Handler> {
 
         let aspect_ratio = win.height() as f32 / win.width() as f32;
         
@@ -39,39 +51,25 @@ impl Handler {
         let texture = Texture2D::new()?;
         texture.bind();
 
-        texture.parameter_2d(gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-        texture.parameter_2d(gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+        texture.set_sampler(&SamplerDesc::nearest().wrap(Wrap::Repeat));
 
         let mut img = PixelArray::load_png_from_path(IMG_PATH).unwrap();
         img.flipv();
         texture.set_image_2d(img);
         texture.generate_mipmap();
 
-        let vao = VertexArray::new()?;
-        vao.bind();
-
-        let vbo = Buffer::new()?;
+        let mut vbo = Buffer::new()?;
         vbo.bind(BufferType::Array);
-        vbo.data::<f32>(BufferType::Array, &vertices, gl::STATIC_DRAW);
+        vbo.data(BufferType::Array, &vertices, gl::STATIC_DRAW);
 
-        let vertex_shader = Shader::new(&VERT_SHADER, gl::VERTEX_SHADER)?;
-        let fragment_shader = Shader::new(&FRAG_SHADER, gl::FRAGMENT_SHADER)?;
+        let vertex_shader = Shader::new(&VERT_SHADER, ShaderStage::Vertex)?;
+        let fragment_shader = Shader::new(&FRAG_SHADER, ShaderStage::Fragment)?;
         let program = Program::new(&[vertex_shader, fragment_shader])?;
         program.use_program();
 
-        let pos_attrib = vao.get_attrib_location(&program, "position");
-        let col_attrib = vao.get_attrib_location(&program, "vertex_color");
-        let tex_coord_attrib = vao.get_attrib_location(&program, "tex_coord");
-
-        vao.enable_vertex_attrib(pos_attrib as u32);
-        vao.enable_vertex_attrib(col_attrib as u32);
-        vao.enable_vertex_attrib(tex_coord_attrib as u32);
-
-        vao.vertex_attrib_pointer::<f32>(pos_attrib as u32, 2, gl::FLOAT, false, 7, 0);
-        vao.vertex_attrib_pointer::<f32>(col_attrib as u32, 3, gl::FLOAT, false, 7, 2);
-        vao.vertex_attrib_pointer::<f32>(tex_coord_attrib as u32, 2, gl::FLOAT, false, 7, 5);
+        let vao = VertexArray::with_layout::<Vertex>(&program, &vbo)?;
 
-        Ok(Handler {aspect_ratio, vao, texture, program})
+        Ok(Handler {aspect_ratio, vao, _vbo: vbo, texture, program})
 
     }
 }
@@ -79,13 +77,11 @@ impl Handler {
 impl WindowHandler for Handler {
     fn on_draw(&mut self) -> HandlerResult<()> {
         unsafe {
-            let aspect_ratio_uniform = Uniform::new(&self.program, "aspect_ratio")?;
-            aspect_ratio_uniform.uniform1f(self.aspect_ratio);
+            self.program.set("aspect_ratio", self.aspect_ratio)?;
             gl::ClearColor(1.0, 1.0, 1.0, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
             self.texture.bind();
-            self.vao.bind();
-            gl::DrawArrays(gl::TRIANGLES, 0, 12);
+            self.vao.draw_arrays(PrimitiveMode::Triangles, 0, 12);
             self.vao.unbind();
             self.texture.unbind();
         }
@@ -104,7 +100,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let render_handler = Handler::new(&window)?;
 
     // Event handling
-    app.run_loop(window, render_handler);
+    app.run_loop(window, render_handler)?;
     Ok(())
 }
 
@@ -1,4 +1,4 @@
-use elara_gfx::{gl_info, WindowHandler, HandlerResult};
+use elara_gfx::{gl_info, WindowHandler, GfxResult, HandlerResult};
 use std::error::Error;
 use elara_gfx::GLWindow;
 use elara_log::prelude::*;
@@ -9,14 +9,19 @@ struct Handler {
 }
 
 impl Handler {
-    fn new(win: &GLWindow) -> Result<Handler, String> {
+    fn new(win: &GLWindow) -> GfxResult<Handler> {
         let mut renderer = TextRenderer::new(win)?;
-        renderer.load("resources/OpenSans-Regular.ttf", 48);
+        renderer.load("resources/OpenSans-Regular.ttf", 48)?;
         Ok(Handler { renderer })
     }
 }
 
 impl WindowHandler for Handler {
+    fn on_resize(&mut self, width: i32, height: i32) -> HandlerResult<()> {
+        self.renderer.resize(width, height);
+        Ok(())
+    }
+
     fn on_draw(&mut self) -> HandlerResult<()> {
         unsafe {
             gl::ClearColor(0.2, 0.3, 0.3, 1.0);
@@ -40,7 +45,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let render_handler = Handler::new(&window)?;
 
     // Event handling
-    app.run_loop(window, render_handler);
+    app.run_loop(window, render_handler)?;
     Ok(())
 }
 
@@ -1,6 +1,6 @@
 // Renders a triangle with `elara-gfx`
-use elara_gfx::{gl_info, Buffer, BufferType, Program, Shader, VertexArray};
-use elara_gfx::{GLWindow, HandlerResult, WindowHandler};
+use elara_gfx::{gl_info, PrimitiveMode, Buffer, BufferType, Program, Shader, ShaderStage, VertexArray};
+use elara_gfx::{GLWindow, GfxResult, HandlerResult, WindowHandler};
 use elara_log::prelude::*;
 use std::error::Error;
 use std::time::{Duration, Instant};
@@ -10,12 +10,14 @@ const FRAG_SHADER: &str = include_str!("shaders/triangle.frag");
 
 struct Handler {
     vao: VertexArray,
+    _vbo: Buffer<f32>,
+    _program: Program,
     frame_count: u32,
     start_time: Instant,
 }
 
 impl Handler {
-    fn new() -> Result<Handler, String> {
+    fn new() -> GfxResult<Handler> {
         let start_time = Instant::now();
         let frame_count = 0;
 
@@ -31,22 +33,24 @@ impl Handler {
         let vao = VertexArray::new()?;
         vao.bind();
 
-        let vbo = Buffer::new()?;
+        let mut vbo = Buffer::new()?;
         vbo.bind(BufferType::Array);
-        vbo.data::<f32>(BufferType::Array, &vertices, gl::STATIC_DRAW);
+        vbo.data(BufferType::Array, &vertices, gl::STATIC_DRAW);
         vao.vertex_attrib_pointer::<f32>(0, 3, gl::FLOAT, false, 0, 0);
         vao.enable_vertex_attrib(0);
 
         vao.unbind();
         vbo.unbind(BufferType::Array);
 
-        let vertex_shader = Shader::new(&VERT_SHADER, gl::VERTEX_SHADER)?;
-        let fragment_shader = Shader::new(&FRAG_SHADER, gl::FRAGMENT_SHADER)?;
+        let vertex_shader = Shader::new(&VERT_SHADER, ShaderStage::Vertex)?;
+        let fragment_shader = Shader::new(&FRAG_SHADER, ShaderStage::Fragment)?;
         let program = Program::new(&[vertex_shader, fragment_shader])?;
         program.use_program();
 
         Ok(Handler {
             vao,
+            _vbo: vbo,
+            _program: program,
             frame_count,
             start_time,
         })
@@ -77,8 +81,7 @@ impl WindowHandler for Handler {
         unsafe {
             gl::ClearColor(1.0, 1.0, 1.0, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
-            self.vao.bind();
-            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            self.vao.draw_arrays(PrimitiveMode::Triangles, 0, 3);
             self.vao.unbind();
         }
         self.add_frame();
@@ -105,6 +108,6 @@ fn main() -> Result<(), Box<dyn Error>> {
     let render_handler = Handler::new()?;
 
     // Event handling
-    app.run_loop(window, render_handler);
+    app.run_loop(window, render_handler)?;
     Ok(())
 }
@@ -1,5 +1,5 @@
 // This demo renders a basic UI with elara-gfx
-use elara_gfx::{gl_info, GLWindow, WindowHandler};
+use elara_gfx::{gl_info, GLWindow, GfxResult, HandlerResult, WindowHandler};
 use elara_gfx::canvas::{clear_color, TextRenderer, RectRenderer, RectStyle, LineRenderer, Color};
 use elara_log::prelude::*;
 use std::error::Error;
@@ -13,10 +13,10 @@ struct Handler {
 }
 
 impl Handler {
-    fn new(win: &GLWindow) -> Result<Handler, String> {
+    fn new(win: &GLWindow) -> GfxResult<Handler> {
     	let resolution = (win.width() as i32, win.height() as i32);
         let mut text_renderer = TextRenderer::new(win)?;
-        text_renderer.load("resources/OpenSans-Regular.ttf", 40);
+        text_renderer.load("resources/OpenSans-Regular.ttf", 40)?;
         let rect_renderer = RectRenderer::new()?;
         let line_renderer = LineRenderer::new()?;
         Ok(Handler{ resolution, text_renderer, rect_renderer, line_renderer })
@@ -24,7 +24,13 @@ impl Handler {
 }
 
 impl WindowHandler for Handler {
-    fn on_draw(&mut self) -> Result<(), String> {
+    fn on_resize(&mut self, width: i32, height: i32) -> HandlerResult<()> {
+        self.text_renderer.resize(width, height);
+        self.resolution = (width, height);
+        Ok(())
+    }
+
+    fn on_draw(&mut self) -> HandlerResult<()> {
         let now = Instant::now();
         clear_color(Color(19, 19, 20, 1.0));
         self.rect_renderer.render_rect(RectStyle::new()
@@ -96,7 +102,7 @@ impl WindowHandler for Handler {
         Ok(())
     }
 
-    fn post_draw(&mut self) -> Result<(), String> {
+    fn post_draw(&mut self) -> HandlerResult<()> {
    		let img = self.save_rendering(self.resolution.0 as i32, self.resolution.1 as i32).unwrap();
         img.save_as_ppm("ui-render.ppm").unwrap();
    		Ok(())
@@ -117,6 +123,6 @@ fn main() -> Result<(), Box<dyn Error>> {
     let render_handler = Handler::new(&window)?;
 
     // Event handling
-    app.run_loop(window, render_handler);
+    app.run_loop(window, render_handler)?;
     Ok(())
 }
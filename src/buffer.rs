@@ -0,0 +1,483 @@
+//! Typed GPU buffers, mapping guards and streaming
+use crate::debug::object_label;
+use crate::{types, BufferType, GfxError, GfxResult, GlCapabilities, Normalized};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::time::Duration;
+
+/// Types for which any bytes are a valid value, so that buffer
+/// contents written by the GPU can be read back as them.
+///
+/// # Safety
+///
+/// The type must have no invalid bit patterns (no `bool`, `char`,
+/// enums, references or `NonZero*`) and be made only of such
+/// types, e.g. a `#[repr(C)]` vertex struct of numbers and arrays:
+///
+/// ```ignore
+/// unsafe impl Pod for Vertex {}
+/// ```
+pub unsafe trait Pod: Copy {}
+
+macro_rules! impl_pod {
+    ($($ty:ty),*) => {
+        $(unsafe impl Pod for $ty {})*
+    };
+}
+
+impl_pod!(u8, i8, u16, i16, u32, i32, u64, i64, usize, isize, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+// `#[repr(transparent)]`
+unsafe impl<T: Pod> Pod for Normalized<T> {}
+
+// A buffer object holding `len` elements of `T`; reading it
+// back needs `T: Pod`, uploading only `T: Copy`
+#[derive(Debug)]
+pub struct Buffer<T: Copy> {
+    id: types::GLuint,
+    len: usize,
+    usage: types::GLenum,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Copy> Buffer<T> {
+    pub fn new() -> GfxResult<Buffer<T>> {
+        let mut buffer = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut buffer);
+        }
+        if buffer != 0 {
+            Ok(Buffer { id: buffer, len: 0, usage: gl::STATIC_DRAW, _marker: PhantomData })
+        } else {
+            Err(GfxError::ObjectCreationError("Buffer"))
+        }
+    }
+
+    pub fn from_slice(buffer_type: BufferType, data: &[T], usage: types::GLenum) -> GfxResult<Buffer<T>> {
+        let mut buffer = Buffer::new()?;
+        buffer.data(buffer_type, data, usage);
+        Ok(buffer)
+    }
+
+    pub fn id(&self) -> types::GLuint {
+        self.id
+    }
+
+    // Number of elements the buffer holds
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn size_bytes(&self) -> usize {
+        self.len * std::mem::size_of::<T>()
+    }
+
+    pub fn usage(&self) -> types::GLenum {
+        self.usage
+    }
+
+    pub fn bind(&self, buffer_type: BufferType) {
+        unsafe { gl::BindBuffer(buffer_type as types::GLenum, self.id) }
+    }
+
+    pub fn unbind(&self, buffer_type: BufferType) {
+        unsafe { gl::BindBuffer(buffer_type as types::GLenum, 0) }
+    }
+
+    // Binds the whole buffer to an indexed binding point, e.g.
+    // `binding = N` of a shader storage or uniform block; only
+    // valid for ShaderStorage and Uniform
+    pub fn bind_base(&self, buffer_type: BufferType, binding: u32) {
+        unsafe { gl::BindBufferBase(buffer_type as types::GLenum, binding, self.id) }
+    }
+
+    // Binds `len` elements starting at `offset` to an indexed
+    // binding point; `offset` must respect the alignment the
+    // context requires for that kind of binding
+    pub fn bind_range(&self, buffer_type: BufferType, binding: u32, offset: usize, len: usize) -> GfxResult<()> {
+        self.check_range(offset, len)?;
+        unsafe {
+            gl::BindBufferRange(
+                buffer_type as types::GLenum,
+                binding,
+                self.id,
+                self.byte_offset(offset),
+                (len * std::mem::size_of::<T>()) as types::GLsizeiptr,
+            );
+        }
+        Ok(())
+    }
+
+    pub fn set_label(&self, label: &str) {
+        object_label(gl::BUFFER, self.id, label);
+    }
+
+    // Binds the buffer to `buffer_type` and replaces its
+    // storage with a copy of `data`
+    pub fn data(&mut self, buffer_type: BufferType, data: &[T], usage: types::GLenum) {
+        self.bind(buffer_type);
+        // An empty slice may dangle, so don't hand its pointer to GL
+        let ptr = if data.is_empty() { std::ptr::null() } else { data.as_ptr() as *const types::c_void };
+        unsafe {
+            gl::BufferData(buffer_type as types::GLenum, std::mem::size_of_val(data) as types::GLsizeiptr, ptr, usage);
+        }
+        self.len = data.len();
+        self.usage = usage;
+    }
+
+    // Binds the buffer to `buffer_type` and allocates space
+    // for `len` elements without copying data to it
+    pub fn data_empty(&mut self, buffer_type: BufferType, len: usize, usage: types::GLenum) {
+        self.bind(buffer_type);
+        unsafe {
+            gl::BufferData(buffer_type as types::GLenum, (len * std::mem::size_of::<T>()) as types::GLsizeiptr, std::ptr::null(), usage);
+        }
+        self.len = len;
+        self.usage = usage;
+    }
+
+    // The methods below go through the copy binding points so
+    // that they don't disturb the vertex array or other bindings
+
+    // Overwrites the elements starting at `offset`
+    pub fn update(&self, offset: usize, data: &[T]) -> GfxResult<()> {
+        self.check_range(offset, data.len())?;
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.bind(BufferType::CopyWrite);
+        unsafe {
+            gl::BufferSubData(
+                gl::COPY_WRITE_BUFFER,
+                self.byte_offset(offset),
+                std::mem::size_of_val(data) as types::GLsizeiptr,
+                data.as_ptr() as *const types::c_void,
+            );
+        }
+        self.unbind(BufferType::CopyWrite);
+        Ok(())
+    }
+
+    // Detaches the current storage so the driver can hand out
+    // fresh memory instead of waiting for draws still using it
+    pub fn orphan(&self) {
+        self.bind(BufferType::CopyWrite);
+        unsafe {
+            gl::BufferData(gl::COPY_WRITE_BUFFER, self.size_bytes() as types::GLsizeiptr, std::ptr::null(), self.usage);
+        }
+        self.unbind(BufferType::CopyWrite);
+    }
+
+    // Orphans the buffer and refills it with `data`, the usual
+    // way to stream data that changes every frame; grows the
+    // buffer when `data` doesn't fit
+    pub fn refill(&mut self, data: &[T]) {
+        if data.len() > self.len {
+            self.data(BufferType::CopyWrite, data, self.usage);
+            self.unbind(BufferType::CopyWrite);
+            return;
+        }
+        self.orphan();
+        // Can't fail, the range was checked above
+        let _ = self.update(0, data);
+    }
+
+    // Copies `len` elements from `src` on the GPU without a
+    // round trip through client memory
+    pub fn copy_from(&self, src: &Buffer<T>, src_offset: usize, dst_offset: usize, len: usize) -> GfxResult<()> {
+        src.check_range(src_offset, len)?;
+        self.check_range(dst_offset, len)?;
+        src.bind(BufferType::CopyRead);
+        self.bind(BufferType::CopyWrite);
+        unsafe {
+            gl::CopyBufferSubData(
+                gl::COPY_READ_BUFFER,
+                gl::COPY_WRITE_BUFFER,
+                src.byte_offset(src_offset),
+                self.byte_offset(dst_offset),
+                (len * std::mem::size_of::<T>()) as types::GLsizeiptr,
+            );
+        }
+        src.unbind(BufferType::CopyRead);
+        self.unbind(BufferType::CopyWrite);
+        Ok(())
+    }
+
+    fn map(&self, offset: usize, len: usize, access: types::GLbitfield) -> GfxResult<*mut T> {
+        self.check_range(offset, len)?;
+        if len == 0 {
+            return Ok(std::ptr::NonNull::dangling().as_ptr());
+        }
+        self.bind(BufferType::CopyWrite);
+        let ptr = unsafe {
+            gl::MapBufferRange(
+                gl::COPY_WRITE_BUFFER,
+                self.byte_offset(offset),
+                (len * std::mem::size_of::<T>()) as types::GLsizeiptr,
+                access,
+            )
+        };
+        self.unbind(BufferType::CopyWrite);
+        if ptr.is_null() {
+            Err(GfxError::BufferMapError)
+        } else {
+            Ok(ptr as *mut T)
+        }
+    }
+
+    fn unmap(&self, len: usize) {
+        if len == 0 {
+            return;
+        }
+        self.bind(BufferType::CopyWrite);
+        unsafe {
+            gl::UnmapBuffer(gl::COPY_WRITE_BUFFER);
+        }
+        self.unbind(BufferType::CopyWrite);
+    }
+
+    fn check_range(&self, offset: usize, len: usize) -> GfxResult<()> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.len => Ok(()),
+            _ => Err(GfxError::BufferOutOfBounds { offset, len, capacity: self.len }),
+        }
+    }
+
+    fn byte_offset(&self, offset: usize) -> types::GLintptr {
+        (offset * std::mem::size_of::<T>()) as types::GLintptr
+    }
+}
+
+// Reading turns bytes written by the GPU into `T` values
+impl<T: Pod> Buffer<T> {
+    // Reads back the whole buffer
+    pub fn read(&self) -> Vec<T> {
+        // In range by construction
+        self.read_range(0, self.len).unwrap_or_default()
+    }
+
+    pub fn read_range(&self, offset: usize, len: usize) -> GfxResult<Vec<T>> {
+        self.check_range(offset, len)?;
+        let mut out = Vec::with_capacity(len);
+        if len == 0 {
+            return Ok(out);
+        }
+        self.bind(BufferType::CopyRead);
+        unsafe {
+            gl::GetBufferSubData(
+                gl::COPY_READ_BUFFER,
+                self.byte_offset(offset),
+                (len * std::mem::size_of::<T>()) as types::GLsizeiptr,
+                out.as_mut_ptr() as *mut types::c_void,
+            );
+            out.set_len(len);
+        }
+        self.unbind(BufferType::CopyRead);
+        Ok(out)
+    }
+
+    // Maps `len` elements starting at `offset` for reading,
+    // the buffer is unmapped when the guard is dropped
+    pub fn map_range(&self, offset: usize, len: usize) -> GfxResult<BufferReadGuard<'_, T>> {
+        let ptr = self.map(offset, len, gl::MAP_READ_BIT)?;
+        Ok(BufferReadGuard { buffer: self, ptr, len })
+    }
+
+    // Maps `len` elements starting at `offset` for reading
+    // and writing
+    pub fn map_range_mut(&mut self, offset: usize, len: usize) -> GfxResult<BufferWriteGuard<'_, T>> {
+        let ptr = self.map(offset, len, gl::MAP_READ_BIT | gl::MAP_WRITE_BIT)?;
+        Ok(BufferWriteGuard { buffer: self, ptr, len })
+    }
+}
+
+impl<T: Copy> Drop for Buffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
+
+pub struct BufferReadGuard<'a, T: Pod> {
+    buffer: &'a Buffer<T>,
+    ptr: *const T,
+    len: usize,
+}
+
+impl<T: Pod> Deref for BufferReadGuard<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl<T: Pod> Drop for BufferReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.buffer.unmap(self.len);
+    }
+}
+
+pub struct BufferWriteGuard<'a, T: Pod> {
+    buffer: &'a mut Buffer<T>,
+    ptr: *mut T,
+    len: usize,
+}
+
+impl<T: Pod> Deref for BufferWriteGuard<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl<T: Pod> DerefMut for BufferWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl<T: Pod> Drop for BufferWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.buffer.unmap(self.len);
+    }
+}
+
+// How long `begin_frame()` waits for the GPU to release a
+// segment before reporting an error
+const RING_FENCE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// A persistently mapped buffer split into `segments` regions
+// that are written in turn, one per frame; a fence guards each
+// region so the CPU never overwrites data the GPU is still
+// reading, and no frame has to wait for the previous one.
+// Needs OpenGL 4.4 or ARB_buffer_storage.
+//
+// Call `begin_frame()`, `push()` the frame's data and draw
+// using the returned offsets, then call `end_frame()`.
+pub struct RingBuffer<T: Copy> {
+    buffer: Buffer<T>,
+    ptr: *mut T,
+    segment_len: usize,
+    fences: Vec<types::GLsync>,
+    segment: usize,
+    cursor: usize,
+}
+
+impl<T: Copy> RingBuffer<T> {
+    // Triple buffering (`segments` = 3) is usually enough
+    pub fn new(segment_len: usize, segments: usize) -> GfxResult<RingBuffer<T>> {
+        if !GlCapabilities::query().supports_buffer_storage() {
+            return Err(GfxError::Unsupported("persistently mapped buffers (ARB_buffer_storage)"));
+        }
+        let mut buffer = Buffer::new()?;
+        let len = segment_len * segments.max(1);
+        let size = (len * std::mem::size_of::<T>()) as types::GLsizeiptr;
+        let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+        buffer.bind(BufferType::CopyWrite);
+        let ptr = unsafe {
+            gl::BufferStorage(gl::COPY_WRITE_BUFFER, size, std::ptr::null(), flags);
+            gl::MapBufferRange(gl::COPY_WRITE_BUFFER, 0, size, flags)
+        };
+        buffer.unbind(BufferType::CopyWrite);
+        if ptr.is_null() {
+            return Err(GfxError::BufferMapError);
+        }
+        buffer.len = len;
+        buffer.usage = gl::STREAM_DRAW;
+        Ok(RingBuffer {
+            buffer,
+            ptr: ptr as *mut T,
+            segment_len,
+            fences: vec![std::ptr::null(); segments.max(1)],
+            segment: 0,
+            cursor: 0,
+        })
+    }
+
+    // The underlying buffer, bind it as a vertex, index
+    // or uniform buffer like any other
+    pub fn buffer(&self) -> &Buffer<T> {
+        &self.buffer
+    }
+
+    pub fn segment_len(&self) -> usize {
+        self.segment_len
+    }
+
+    // Moves to the next segment, waiting for the GPU to finish
+    // with it if needed. If it doesn't in time the segment stays
+    // guarded and `push()` fails until the next `begin_frame()`.
+    pub fn begin_frame(&mut self) -> GfxResult<()> {
+        self.segment = (self.segment + 1) % self.fences.len();
+        self.cursor = 0;
+        let fence = self.fences[self.segment];
+        if fence.is_null() {
+            return Ok(());
+        }
+        let status = unsafe { gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, RING_FENCE_TIMEOUT.as_nanos() as u64) };
+        match status {
+            gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => {
+                unsafe {
+                    gl::DeleteSync(fence);
+                }
+                self.fences[self.segment] = std::ptr::null();
+                Ok(())
+            }
+            _ => {
+                // Leave no room, so nothing overwrites what the
+                // GPU may still be reading
+                self.cursor = self.segment_len;
+                Err(GfxError::FenceWaitError(if status == gl::TIMEOUT_EXPIRED { "timed out" } else { "failed" }))
+            }
+        }
+    }
+
+    // Copies `data` into the current segment and returns its
+    // offset in elements from the start of the buffer
+    pub fn push(&mut self, data: &[T]) -> GfxResult<usize> {
+        if self.cursor + data.len() > self.segment_len {
+            return Err(GfxError::BufferOutOfBounds { offset: self.cursor, len: data.len(), capacity: self.segment_len });
+        }
+        let offset = self.segment * self.segment_len + self.cursor;
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), self.ptr.add(offset), data.len());
+        }
+        self.cursor += data.len();
+        Ok(offset)
+    }
+
+    // Marks the current segment as in use by the
+    // draw calls issued since `begin_frame()`
+    pub fn end_frame(&mut self) {
+        let fence = unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+        // A fence left by a failed wait is covered by the new one
+        let previous = std::mem::replace(&mut self.fences[self.segment], fence);
+        if !previous.is_null() {
+            unsafe {
+                gl::DeleteSync(previous);
+            }
+        }
+    }
+}
+
+impl<T: Copy> Drop for RingBuffer<T> {
+    fn drop(&mut self) {
+        // The mapping goes away with the buffer itself
+        for fence in &self.fences {
+            if !fence.is_null() {
+                unsafe {
+                    gl::DeleteSync(*fence);
+                }
+            }
+        }
+    }
+}
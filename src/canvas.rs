@@ -1,13 +1,20 @@
 use std::collections::HashMap;
 use std::f32::consts::PI;
-use crate::{GLWindow, VertexArray, Texture2D, Program, Draw, PixelArray, WindowHandler, Buffer, BufferType, Shader, HandlerResult, Uniform};
-use crate::types;
+use crate::{GLSurface, VertexArray, Texture2D, Program, Draw, PixelArray, WindowHandler, Buffer, BufferType, Shader, ShaderSource, ShaderStage, HandlerResult, GfxResult, DebugGroup, PrimitiveMode, TextureFormat, SamplerDesc, Wrap, enable_alpha_blend, set_unpack_alignment};
+use crate::impl_vertex_layout;
 use std::ffi::OsStr;
 use freetype::Library;
 use freetype::face::LoadFlag;
 
+// The built-in shaders leave out `#version`, it is added
+// when they are compiled
+const CANVAS_GLSL_VERSION: &str = "330 core";
+
+fn canvas_shader(name: &str, source: &str, stage: ShaderStage) -> GfxResult<Shader> {
+    Shader::from_source(&ShaderSource::new(name, source).version(CANVAS_GLSL_VERSION), stage)
+}
+
 const TEXT_VERTEX_SHADER: &'static str = r#"
-#version 330 core
 in vec4 vertex;
 out vec2 TexCoord;
 
@@ -18,7 +25,6 @@ void main() {
 "#;
 
 const TEXT_FRAGMENT_SHADER: &'static str = r#"
-#version 330 core
 in vec2 TexCoord;
 out vec4 FragColor;
 
@@ -245,7 +251,6 @@ fn convert_ranges(value: f32, old_min: f32, old_max: f32, new_min: f32, new_max:
 }
 
 const CANVAS_VERT_SHADER: &'static str = r#"
-#version 330 core
 in vec2 position;
 in vec2 tex_coord;
 in vec4 vertex_color;
@@ -261,7 +266,6 @@ void main() {
 "#;
 
 const CANVAS_FRAG_SHADER: &'static str = r#"
-#version 330 core
 in vec2 TexCoord;
 in vec4 VertexColor;
 uniform sampler2D uTexture;
@@ -317,6 +321,17 @@ pub fn abs_normalize_2d(x: [f32; 2], norm: f32, scale: f32) -> [f32; 2] {
 #[derive(Clone, Debug)]
 pub struct Vertex(f32, f32);
 
+// Layout of the vertices produced by Canvas::to_vertices()
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CanvasVertex {
+    pub position: [f32; 2],
+    pub vertex_color: [f32; 4],
+    pub tex_coord: [f32; 2],
+}
+
+impl_vertex_layout!(CanvasVertex { position, vertex_color, tex_coord });
+
 #[derive(Clone, Debug)]
 pub struct Color(pub i32, pub i32, pub i32, pub f32);
 
@@ -347,7 +362,7 @@ pub struct CharCoord {
 }
 
 impl Canvas {
-    pub fn new(win: &GLWindow) -> Canvas {
+    pub fn new(win: &(impl GLSurface + ?Sized)) -> Canvas {
         Canvas { 
             points: Vec::new(), 
             background: Color(255, 255, 255, 1.0),
@@ -589,15 +604,15 @@ impl Canvas {
 
 #[derive(Debug)]
 struct Character {
-    pub texture_id: i32,
+    pub texture: Texture2D,
     pub size: (i32, i32),
     pub bearing: (i32, i32),
     pub advance: i32
 }
 
 impl Character {
-    fn new(texture_id: i32, size: (i32, i32), bearing: (i32, i32), advance: i32) -> Character {
-        Character { texture_id, size, bearing, advance }
+    fn new(texture: Texture2D, size: (i32, i32), bearing: (i32, i32), advance: i32) -> Character {
+        Character { texture, size, bearing, advance }
     }
 }
 
@@ -606,22 +621,25 @@ pub struct TextRenderer {
     win_height: i32,
     program: Program,
     vao: VertexArray,
-    vbo: Buffer,
+    vbo: Buffer<f32>,
     characters: HashMap<char, Character>
 }
 
 impl TextRenderer {
-    pub fn new(win: &GLWindow) -> Result<TextRenderer, String> {
-        let (win_width, win_height) = (win.width, win.height);
-        let vertex_shader = Shader::new(&TEXT_VERTEX_SHADER, gl::VERTEX_SHADER)?;
-        let fragment_shader = Shader::new(&TEXT_FRAGMENT_SHADER, gl::FRAGMENT_SHADER)?;
+    pub fn new(win: &impl GLSurface) -> GfxResult<TextRenderer> {
+        let (win_width, win_height) = (win.width(), win.height());
+        let vertex_shader = canvas_shader("text.vert", TEXT_VERTEX_SHADER, ShaderStage::Vertex)?;
+        let fragment_shader = canvas_shader("text.frag", TEXT_FRAGMENT_SHADER, ShaderStage::Fragment)?;
         let program = Program::new(&[vertex_shader, fragment_shader])?;
+        program.set_label("TextRenderer program");
 
         let vao = VertexArray::new()?;
-        let vbo = Buffer::new()?;
+        let mut vbo = Buffer::new()?;
         vao.bind();
         vbo.bind(BufferType::Array);
-        vbo.data_empty::<f32>(BufferType::Array, 24, gl::DYNAMIC_DRAW);
+        vao.set_label("TextRenderer vertex array");
+        vbo.set_label("TextRenderer vertex buffer");
+        vbo.data_empty(BufferType::Array, 24, gl::DYNAMIC_DRAW);
 
         let vertex_attrib = 0;
         vao.enable_vertex_attrib(vertex_attrib as u32);
@@ -634,17 +652,22 @@ impl TextRenderer {
         Ok(TextRenderer { win_width, win_height, program, vao, vbo, characters })
     }
 
-    pub fn load<F: AsRef<OsStr>>(&mut self, font: F, size: u32) {
-        if !self.characters.is_empty() {
-            self.characters.clear();
-        }
+    // Should be called from WindowHandler::on_resize() so that
+    // text keeps its pixel size and position after a resize
+    pub fn resize(&mut self, width: i32, height: i32) {
+        self.win_width = width;
+        self.win_height = height;
+    }
 
-        let ft = Library::init().unwrap();
-        let face = ft.new_face(font, 0).unwrap();
-        face.set_pixel_sizes(0, size).unwrap();
-        unsafe {
-            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
-        }
+    pub fn load<F: AsRef<OsStr>>(&mut self, font: F, size: u32) -> GfxResult<()> {
+        // Dropping the old glyphs frees their textures
+        self.characters.clear();
+
+        let ft = Library::init()?;
+        let face = ft.new_face(font, 0)?;
+        face.set_pixel_sizes(0, size)?;
+        // Glyph rows are tightly packed
+        set_unpack_alignment(1)?;
         // Enable blending
         unsafe {
             gl::Enable(gl::CULL_FACE);
@@ -654,52 +677,34 @@ impl TextRenderer {
 
         // Load first 128 characters of ASCII set
         for c in 0..128 as u8 {
-            face.load_char(c as usize, LoadFlag::RENDER).unwrap();
-            unsafe {
-                let mut texture = 0;
-                gl::GenTextures(1, &mut texture);
-                gl::BindTexture(gl::TEXTURE_2D, texture);
-                gl::TexImage2D(
-                    gl::TEXTURE_2D,
-                    0,
-                    gl::RED as i32,
-                    face.glyph().bitmap().width(),
-                    face.glyph().bitmap().rows(),
-                    0,
-                    gl::RED,
-                    gl::UNSIGNED_BYTE,
-                    face.glyph().bitmap().buffer().as_ptr() as *const types::c_void
-                );
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-
-                let character = Character::new(
-                    texture as i32,
-                    (face.glyph().bitmap().width(), face.glyph().bitmap().rows()),
-                    (face.glyph().bitmap_left(), face.glyph().bitmap_top()),
-                    face.glyph().advance().x as i32
-                );
-
-                self.characters.insert(c as char, character);
-            }
+            face.load_char(c as usize, LoadFlag::RENDER)?;
+            let texture = Texture2D::new()?;
+            let bitmap = face.glyph().bitmap();
+            texture.set_image_data(TextureFormat::R8, bitmap.width(), bitmap.rows(), bitmap.buffer())?;
+            texture.set_sampler(&SamplerDesc::linear());
+
+            let character = Character::new(
+                texture,
+                (face.glyph().bitmap().width(), face.glyph().bitmap().rows()),
+                (face.glyph().bitmap_left(), face.glyph().bitmap_top()),
+                face.glyph().advance().x as i32
+            );
+
+            self.characters.insert(c as char, character);
         }
         unsafe {
             gl::BindTexture(gl::TEXTURE_2D, 0);
         }
+        Ok(())
     }
 
-    pub fn render_text(&self, text: &str, x0: i32, y0: i32, scale: f32, color: Color) -> Result<(), String> {
+    pub fn render_text(&self, text: &str, x0: i32, y0: i32, scale: f32, color: Color) -> GfxResult<()> {
+        let _group = DebugGroup::new("TextRenderer::render_text");
         // We convert the (0..x) and (0..y) coordinates to (-width..width) and (-height..height)
         let mut x = convert_ranges(x0 as f32, 0.0, self.win_width as f32, -self.win_width as f32, self.win_width as f32);
         let y = convert_ranges(y0 as f32, 0.0, self.win_height as f32, -self.win_height as f32, self.win_height as f32);
         self.program.use_program();
-        let color_uniform = Uniform::new(&self.program, "textColor")?;
-        color_uniform.uniform3f(color.0 as f32 / 255.0, color.1 as f32 / 255.0, color.2 as f32 / 255.0);
-        unsafe {
-            gl::ActiveTexture(gl::TEXTURE0);
-        }
+        self.program.set("textColor", [color.0 as f32 / 255.0, color.1 as f32 / 255.0, color.2 as f32 / 255.0])?;
         self.vao.bind();
         for c in text.chars() {
             let ch = self.characters.get(&c).unwrap();
@@ -716,14 +721,10 @@ impl TextRenderer {
                 xpos + w, ypos,       1.0,     1.0,
                 xpos + w, ypos + h,   1.0,     0.0  
             ];
-            unsafe {
-                gl::BindTexture(gl::TEXTURE_2D, ch.texture_id as u32);
-                self.vbo.bind(BufferType::Array);
-                self.vbo.subdata(BufferType::Array, 0, &vertices);
-                self.vbo.unbind(BufferType::Array);
-                gl::DrawArrays(gl::TRIANGLES, 0, 6);
-                x += (ch.advance >> 6) as f32 * scale;
-            }
+            ch.texture.bind_to_unit(0);
+            self.vbo.update(0, &vertices)?;
+            self.vao.draw_arrays(PrimitiveMode::Triangles, 0, 6);
+            x += (ch.advance >> 6) as f32 * scale;
         }
         self.vao.unbind();
         unsafe {
@@ -734,7 +735,6 @@ impl TextRenderer {
 }
 
 const RECT_VERTEX_SHADER: &'static str = r#"
-#version 330 core
 in vec2 position;
 
 void main() {
@@ -743,7 +743,6 @@ void main() {
 "#;
 
 const RECT_FRAGMENT_SHADER: &'static str = r#"
-#version 330 core
 uniform vec2 location; // location of the bottom left corner of rect
 uniform vec2 size; // size of the rect in pixels
 uniform vec3 rectColor; // rectangle color
@@ -826,11 +825,11 @@ impl RectStyle {
 pub struct RectRenderer {
     program: Program,
     vao: VertexArray,
-    vbo: Buffer,
+    vbo: Buffer<f32>,
 }
 
 impl RectRenderer {
-    pub fn new() -> Result<RectRenderer, String> {
+    pub fn new() -> GfxResult<RectRenderer> {
         // Enable blending
         unsafe {
             gl::Enable(gl::CULL_FACE);
@@ -838,15 +837,18 @@ impl RectRenderer {
             gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
         }
 
-        let vertex_shader = Shader::new(&RECT_VERTEX_SHADER, gl::VERTEX_SHADER)?;
-        let fragment_shader = Shader::new(&RECT_FRAGMENT_SHADER, gl::FRAGMENT_SHADER)?;
+        let vertex_shader = canvas_shader("rect.vert", RECT_VERTEX_SHADER, ShaderStage::Vertex)?;
+        let fragment_shader = canvas_shader("rect.frag", RECT_FRAGMENT_SHADER, ShaderStage::Fragment)?;
         let program = Program::new(&[vertex_shader, fragment_shader])?;
+        program.set_label("RectRenderer program");
 
         let vao = VertexArray::new()?;
-        let vbo = Buffer::new()?;
+        let mut vbo = Buffer::new()?;
         vao.bind();
         vbo.bind(BufferType::Array);
-        vbo.data_empty::<f32>(BufferType::Array, 12, gl::DYNAMIC_DRAW);
+        vao.set_label("RectRenderer vertex array");
+        vbo.set_label("RectRenderer vertex buffer");
+        vbo.data_empty(BufferType::Array, 12, gl::DYNAMIC_DRAW);
 
         let vertex_attrib = vao.get_attrib_location(&program, "position");
         vao.enable_vertex_attrib(vertex_attrib as u32);
@@ -858,20 +860,15 @@ impl RectRenderer {
         Ok(RectRenderer { program, vao, vbo })
     }
 
-    pub fn render_rect(&self, style: RectStyle) -> Result<(), String> {
+    pub fn render_rect(&self, style: RectStyle) -> GfxResult<()> {
+        let _group = DebugGroup::new("RectRenderer::render_rect");
         self.program.use_program();
-        let location_uniform = Uniform::new(&self.program, "location")?;
-        location_uniform.uniform2f(style.x0, style.y0);
-        let size_uniform = Uniform::new(&self.program, "size")?;
-        size_uniform.uniform2f(style.w, style.h);
-        let color_uniform = Uniform::new(&self.program, "rectColor")?;
-        color_uniform.uniform3f(style.rect_color.0 as f32 / 255.0, style.rect_color.1 as f32 / 255.0, style.rect_color.2 as f32 / 255.0);
-        let border_color_uniform = Uniform::new(&self.program, "borderColor")?;
-        border_color_uniform.uniform3f(style.border_color.0 as f32 / 255.0, style.border_color.1 as f32 / 255.0, style.border_color.2 as f32 / 255.0);
-        let border_thickness_uniform = Uniform::new(&self.program, "borderThickness")?;
-        border_thickness_uniform.uniform1f(style.border_thickness);
-        let border_radius_uniform = Uniform::new(&self.program, "borderRadius")?;
-        border_radius_uniform.uniform1f(style.border_radius);
+        self.program.set("location", [style.x0, style.y0])?;
+        self.program.set("size", [style.w, style.h])?;
+        self.program.set("rectColor", [style.rect_color.0 as f32 / 255.0, style.rect_color.1 as f32 / 255.0, style.rect_color.2 as f32 / 255.0])?;
+        self.program.set("borderColor", [style.border_color.0 as f32 / 255.0, style.border_color.1 as f32 / 255.0, style.border_color.2 as f32 / 255.0])?;
+        self.program.set("borderThickness", style.border_thickness)?;
+        self.program.set("borderRadius", style.border_radius)?;
         self.vao.bind();
         let vertices: [f32; 12] = [
             -1.0, 1.0,            
@@ -882,18 +879,13 @@ impl RectRenderer {
             1.0, -1.0,    
             1.0, 1.0, 
         ];
-        self.vbo.bind(BufferType::Array);
-        self.vbo.subdata(BufferType::Array, 0, &vertices);
-        self.vbo.unbind(BufferType::Array);
-        unsafe {
-            gl::DrawArrays(gl::TRIANGLES, 0, 6);
-        }
+        self.vbo.update(0, &vertices)?;
+        self.vao.draw_arrays(PrimitiveMode::Triangles, 0, 6);
         Ok(())
     }
 }
 
 const LINE_VERTEX_SHADER: &'static str = r#"
-#version 330 core
 in vec2 position;
 
 void main() {
@@ -902,7 +894,6 @@ void main() {
 "#;
 
 const LINE_FRAGMENT_SHADER: &'static str = r#"
-#version 330 core
 uniform vec2 startLocation;
 uniform vec2 endLocation;
 uniform float thickness;
@@ -934,11 +925,11 @@ void main()
 pub struct LineRenderer {
     program: Program,
     vao: VertexArray,
-    vbo: Buffer,
+    vbo: Buffer<f32>,
 }
 
 impl LineRenderer {
-    pub fn new() -> Result<LineRenderer, String> {
+    pub fn new() -> GfxResult<LineRenderer> {
         // Enable blending
         unsafe {
             gl::Enable(gl::CULL_FACE);
@@ -946,15 +937,18 @@ impl LineRenderer {
             gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
         }
 
-        let vertex_shader = Shader::new(&LINE_VERTEX_SHADER, gl::VERTEX_SHADER)?;
-        let fragment_shader = Shader::new(&LINE_FRAGMENT_SHADER, gl::FRAGMENT_SHADER)?;
+        let vertex_shader = canvas_shader("line.vert", LINE_VERTEX_SHADER, ShaderStage::Vertex)?;
+        let fragment_shader = canvas_shader("line.frag", LINE_FRAGMENT_SHADER, ShaderStage::Fragment)?;
         let program = Program::new(&[vertex_shader, fragment_shader])?;
+        program.set_label("LineRenderer program");
 
         let vao = VertexArray::new()?;
-        let vbo = Buffer::new()?;
+        let mut vbo = Buffer::new()?;
         vao.bind();
         vbo.bind(BufferType::Array);
-        vbo.data_empty::<f32>(BufferType::Array, 12, gl::DYNAMIC_DRAW);
+        vao.set_label("LineRenderer vertex array");
+        vbo.set_label("LineRenderer vertex buffer");
+        vbo.data_empty(BufferType::Array, 12, gl::DYNAMIC_DRAW);
 
         let vertex_attrib = vao.get_attrib_location(&program, "position");
         vao.enable_vertex_attrib(vertex_attrib as u32);
@@ -968,16 +962,13 @@ impl LineRenderer {
     // Render a line with start point p1 and end point p2;
     // it is recommended to use render_horizontal_line()
     // or render_vertical_line() instead
-    pub fn render_line(&self, p1: [f32; 2], p2: [f32; 2], thickness: f32, color: Color) -> Result<(), String> {
+    pub fn render_line(&self, p1: [f32; 2], p2: [f32; 2], thickness: f32, color: Color) -> GfxResult<()> {
+        let _group = DebugGroup::new("LineRenderer::render_line");
         self.program.use_program();
-        let start_location_uniform = Uniform::new(&self.program, "startLocation")?;
-        start_location_uniform.uniform2f(p1[0], p1[1]);
-        let end_location_uniform = Uniform::new(&self.program, "endLocation")?;
-        end_location_uniform.uniform2f(p2[0], p2[1]);
-        let thickness_uniform = Uniform::new(&self.program, "thickness")?;
-        thickness_uniform.uniform1f(thickness);
-        let color_uniform = Uniform::new(&self.program, "lineColor")?;
-        color_uniform.uniform3f(color.0 as f32 / 255.0, color.1 as f32 / 255.0, color.2 as f32 / 255.0);
+        self.program.set("startLocation", p1)?;
+        self.program.set("endLocation", p2)?;
+        self.program.set("thickness", thickness)?;
+        self.program.set("lineColor", [color.0 as f32 / 255.0, color.1 as f32 / 255.0, color.2 as f32 / 255.0])?;
 
         self.vao.bind();
         let vertices: [f32; 12] = [
@@ -989,28 +980,26 @@ impl LineRenderer {
             1.0, -1.0,    
             1.0, 1.0, 
         ];
-        self.vbo.bind(BufferType::Array);
-        self.vbo.subdata(BufferType::Array, 0, &vertices);
-        self.vbo.unbind(BufferType::Array);
-        unsafe {
-            gl::DrawArrays(gl::TRIANGLES, 0, 6);
-        }
+        self.vbo.update(0, &vertices)?;
+        self.vao.draw_arrays(PrimitiveMode::Triangles, 0, 6);
         Ok(())
     }
 
     // Renders a vertical line with bottom at (x0, y0) and a height of h
-    pub fn render_vertical_line(&self, x0: i32, y0: i32, h: i32, thickness: f32, color: Color) -> Result<(), String> {
+    pub fn render_vertical_line(&self, x0: i32, y0: i32, h: i32, thickness: f32, color: Color) -> GfxResult<()> {
         self.render_line([x0 as f32, y0 as f32], [x0 as f32, (y0 + h) as f32], thickness, color)
     }
     
     // Renders a horizontal line with left at (x0, y0) and a height of h
-    pub fn render_horizontal_line(&self, x0: i32, y0: i32, w: i32, thickness: f32, color: Color) -> Result<(), String> {
+    pub fn render_horizontal_line(&self, x0: i32, y0: i32, w: i32, thickness: f32, color: Color) -> GfxResult<()> {
         self.render_line([x0 as f32, y0 as f32], [(x0 + w) as f32, y0 as f32], thickness, color)
     }
 }
 
 pub struct CanvasHandler {
     vao: VertexArray,
+    // Only kept alive for the vertex array
+    _vbo: Buffer<f32>,
     vertex_num: usize,
     background: Color,
     program: Program,
@@ -1019,12 +1008,12 @@ pub struct CanvasHandler {
 }
 
 impl CanvasHandler {
-    pub fn new<D>(win: &GLWindow, mut canvas: D, white_text: bool) -> Result<CanvasHandler, String> 
-        where D: Draw + 'static
+    pub fn new<S, D>(win: &S, mut canvas: D, white_text: bool) -> GfxResult<CanvasHandler> 
+        where S: GLSurface, D: Draw + 'static
     {
-        let canvas = canvas.draw(&win)?;
+        let canvas = canvas.draw(win)?;
         let ATLAS_IMG = if white_text { ATLAS_IMG_WHITE } else { ATLAS_IMG_BLACK };
-        let img = PixelArray::load_png(ATLAS_IMG).unwrap();
+        let img = PixelArray::load_png(ATLAS_IMG)?;
         let vertices = &canvas.to_vertices();
         let vertex_num = canvas.len();
         let background = canvas.background();
@@ -1032,44 +1021,33 @@ impl CanvasHandler {
 
         let texture = Texture2D::new()?;
         texture.bind();
-        texture.parameter_2d(gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
-        texture.parameter_2d(gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
-        texture.parameter_2d(gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-        texture.parameter_2d(gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-        texture.enable_alpha_blend();
+        texture.set_label("CanvasHandler texture atlas");
+        texture.set_sampler(&SamplerDesc::linear().wrap(Wrap::Repeat));
+        enable_alpha_blend();
 
         texture.set_image_2d(img);
         texture.generate_mipmap();
 
-        let vao = VertexArray::new()?;
-        vao.bind();
-
-        let vbo = Buffer::new()?;
+        let mut vbo = Buffer::new()?;
         vbo.bind(BufferType::Array);
-        vbo.data::<f32>(BufferType::Array, &vertices, gl::STATIC_DRAW);
+        vbo.set_label("CanvasHandler vertex buffer");
+        vbo.data(BufferType::Array, &vertices, gl::STATIC_DRAW);
 
-        let vertex_shader = Shader::new(&CANVAS_VERT_SHADER, gl::VERTEX_SHADER)?;
-        let fragment_shader = Shader::new(&CANVAS_FRAG_SHADER, gl::FRAGMENT_SHADER)?;
+        let vertex_shader = canvas_shader("canvas.vert", CANVAS_VERT_SHADER, ShaderStage::Vertex)?;
+        let fragment_shader = canvas_shader("canvas.frag", CANVAS_FRAG_SHADER, ShaderStage::Fragment)?;
         let program = Program::new(&[vertex_shader, fragment_shader])?;
+        program.set_label("CanvasHandler program");
         program.use_program();
 
-        let pos_attrib = vao.get_attrib_location(&program, "position");
-        let col_attrib = vao.get_attrib_location(&program, "vertex_color");
-        let tex_coord_attrib = vao.get_attrib_location(&program, "tex_coord");
-
-        vao.enable_vertex_attrib(pos_attrib as u32);
-        vao.enable_vertex_attrib(col_attrib as u32);
-        vao.enable_vertex_attrib(tex_coord_attrib as u32);
-
-        vao.vertex_attrib_pointer::<f32>(pos_attrib as u32, 2, gl::FLOAT, false, 8, 0);
-        vao.vertex_attrib_pointer::<f32>(col_attrib as u32, 4, gl::FLOAT, false, 8, 2);
-        vao.vertex_attrib_pointer::<f32>(tex_coord_attrib as u32, 2, gl::FLOAT, false, 8, 6);
-
+        // Canvas::to_vertices() is a flat list of CanvasVertex
+        let vao = VertexArray::with_layout::<CanvasVertex>(&program, &vbo)?;
+        vao.bind();
+        vao.set_label("CanvasHandler vertex array");
         vao.unbind();
-        vbo.unbind(BufferType::Array);
 
         Ok(CanvasHandler {
             vao,
+            _vbo: vbo,
             vertex_num,
             background,
             program,
@@ -1081,16 +1059,24 @@ impl CanvasHandler {
 
 
 impl WindowHandler for CanvasHandler {
+    fn on_resize(&mut self, width: i32, height: i32) -> HandlerResult<()> {
+        self.aspect_ratio = height as f32 / width as f32;
+        Ok(())
+    }
+
     fn on_draw(&mut self) -> HandlerResult<()> {
+        let _group = DebugGroup::new("CanvasHandler::on_draw");
         unsafe {
             // gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
-            let aspect_ratio_uniform = Uniform::new(&self.program, "aspect_ratio")?;
-            aspect_ratio_uniform.uniform1f(self.aspect_ratio);
+            // The canvas shader declares aspect_ratio without using it,
+            // so most compilers drop it; skip it instead of warning
+            if self.program.uniform_info("aspect_ratio").is_some() {
+                self.program.set("aspect_ratio", self.aspect_ratio)?;
+            }
             gl::ClearColor(self.background.0 as f32 / 255.0, self.background.1 as f32 / 255.0, self.background.2 as f32 / 255.0, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
             self.texture.bind();
-            self.vao.bind();
-            gl::DrawArrays(gl::TRIANGLES, 0, self.vertex_num as i32);
+            self.vao.draw_arrays(PrimitiveMode::Triangles, 0, self.vertex_num);
             self.vao.unbind();
             self.texture.unbind();
         }
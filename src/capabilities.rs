@@ -0,0 +1,250 @@
+//! Querying what the current OpenGL context supports
+//!
+//! `GlCapabilities::query()` must be called with a current
+//! context, e.g. after `GLWindow::get_context()` or once a
+//! `HeadlessContext` has been created. `GlCapabilities::current()`
+//! returns a cached query for checks that run every frame.
+use crate::{gl_get_string, types};
+use elara_log::prelude::*;
+use std::collections::HashSet;
+use std::ffi::CStr;
+use std::sync::{Arc, RwLock};
+
+// From GL 4.6 / EXT_texture_filter_anisotropic, which
+// the generated bindings (GL 4.5) do not include
+pub(crate) const TEXTURE_MAX_ANISOTROPY: types::GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: types::GLenum = 0x84FF;
+
+// Capabilities of the context whose functions were loaded last;
+// GL functions are loaded process-wide, so there is only ever
+// one context they can describe
+static CURRENT: RwLock<Option<Arc<GlCapabilities>>> = RwLock::new(None);
+
+#[derive(Debug, Clone, Default)]
+pub struct GlCapabilities {
+    pub vendor: String,
+    pub renderer: String,
+    pub version_string: String,
+    // (major, minor), e.g. (4, 5)
+    pub gl_version: (u32, u32),
+    // (major, minor), e.g. (4, 50) for GLSL 4.50
+    pub glsl_version: (u32, u32),
+    pub is_gles: bool,
+    pub extensions: HashSet<String>,
+    pub max_texture_size: i32,
+    pub max_3d_texture_size: i32,
+    pub max_array_texture_layers: i32,
+    pub max_cube_map_texture_size: i32,
+    pub max_texture_image_units: i32,
+    pub max_combined_texture_image_units: i32,
+    pub max_samples: i32,
+    pub max_color_attachments: i32,
+    pub max_draw_buffers: i32,
+    pub max_vertex_attribs: i32,
+    pub max_uniform_block_size: i64,
+    pub max_uniform_buffer_bindings: i32,
+    pub max_texture_max_anisotropy: f32,
+    // Zero when compute shaders are not supported
+    pub max_compute_work_group_count: [i32; 3],
+    pub max_compute_work_group_size: [i32; 3],
+    pub max_compute_work_group_invocations: i32,
+}
+
+impl GlCapabilities {
+    pub fn query() -> GlCapabilities {
+        let version_string = gl_get_string(gl::VERSION).unwrap_or("").to_string();
+        let glsl_string = gl_get_string(gl::SHADING_LANGUAGE_VERSION).unwrap_or("");
+        let mut caps = GlCapabilities {
+            vendor: gl_get_string(gl::VENDOR).unwrap_or("").to_string(),
+            renderer: gl_get_string(gl::RENDERER).unwrap_or("").to_string(),
+            gl_version: parse_version(&version_string).unwrap_or((0, 0)),
+            glsl_version: parse_version(glsl_string).unwrap_or((0, 0)),
+            is_gles: version_string.starts_with("OpenGL ES"),
+            version_string,
+            extensions: query_extensions(),
+            ..Default::default()
+        };
+
+        caps.max_texture_size = get_integer(gl::MAX_TEXTURE_SIZE);
+        caps.max_3d_texture_size = get_integer(gl::MAX_3D_TEXTURE_SIZE);
+        caps.max_cube_map_texture_size = get_integer(gl::MAX_CUBE_MAP_TEXTURE_SIZE);
+        caps.max_texture_image_units = get_integer(gl::MAX_TEXTURE_IMAGE_UNITS);
+        caps.max_combined_texture_image_units = get_integer(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS);
+        caps.max_vertex_attribs = get_integer(gl::MAX_VERTEX_ATTRIBS);
+        caps.max_draw_buffers = get_integer(gl::MAX_DRAW_BUFFERS);
+        if caps.version_at_least(3, 0) {
+            caps.max_array_texture_layers = get_integer(gl::MAX_ARRAY_TEXTURE_LAYERS);
+            caps.max_samples = get_integer(gl::MAX_SAMPLES);
+            caps.max_color_attachments = get_integer(gl::MAX_COLOR_ATTACHMENTS);
+        }
+        if caps.version_at_least(3, 1) {
+            let mut block_size = 0;
+            unsafe {
+                gl::GetInteger64v(gl::MAX_UNIFORM_BLOCK_SIZE, &mut block_size);
+            }
+            caps.max_uniform_block_size = block_size;
+            caps.max_uniform_buffer_bindings = get_integer(gl::MAX_UNIFORM_BUFFER_BINDINGS);
+        }
+        if caps.supports_anisotropic_filtering() {
+            unsafe {
+                gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut caps.max_texture_max_anisotropy);
+            }
+        }
+        if caps.supports_compute() {
+            for i in 0..3 {
+                unsafe {
+                    gl::GetIntegeri_v(gl::MAX_COMPUTE_WORK_GROUP_COUNT, i, &mut caps.max_compute_work_group_count[i as usize]);
+                    gl::GetIntegeri_v(gl::MAX_COMPUTE_WORK_GROUP_SIZE, i, &mut caps.max_compute_work_group_size[i as usize]);
+                }
+            }
+            caps.max_compute_work_group_invocations = get_integer(gl::MAX_COMPUTE_WORK_GROUP_INVOCATIONS);
+        }
+        caps
+    }
+
+    // query() once per context, then the cached result; cheap
+    // enough for checks on every draw or bind
+    pub fn current() -> Arc<GlCapabilities> {
+        if let Some(caps) = CURRENT.read().unwrap_or_else(|err| err.into_inner()).as_ref() {
+            return Arc::clone(caps);
+        }
+        let caps = Arc::new(GlCapabilities::query());
+        // Without a current context everything reads as zero,
+        // don't keep that around
+        if caps.gl_version != (0, 0) {
+            *CURRENT.write().unwrap_or_else(|err| err.into_inner()) = Some(Arc::clone(&caps));
+        }
+        caps
+    }
+
+    pub fn version_at_least(&self, major: u32, minor: u32) -> bool {
+        self.gl_version >= (major, minor)
+    }
+
+    // GLSL version as used in `#version` directives, e.g. 450
+    pub fn glsl_version_number(&self) -> u32 {
+        self.glsl_version.0 * 100 + self.glsl_version.1
+    }
+
+    // Extension names include their prefix, e.g. "GL_KHR_debug"
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.contains(name)
+    }
+
+    pub fn supports_compute(&self) -> bool {
+        self.version_at_least(4, 3) || self.has_extension("GL_ARB_compute_shader")
+    }
+
+    pub fn supports_debug_output(&self) -> bool {
+        self.version_at_least(4, 3) || self.has_extension("GL_KHR_debug")
+    }
+
+    pub fn supports_anisotropic_filtering(&self) -> bool {
+        self.version_at_least(4, 6)
+            || self.has_extension("GL_ARB_texture_filter_anisotropic")
+            || self.has_extension("GL_EXT_texture_filter_anisotropic")
+    }
+
+    pub fn supports_program_binary(&self) -> bool {
+        self.version_at_least(4, 1) || self.has_extension("GL_ARB_get_program_binary")
+    }
+
+    pub fn supports_texture_storage(&self) -> bool {
+        self.version_at_least(4, 2) || self.has_extension("GL_ARB_texture_storage")
+    }
+
+    pub fn supports_buffer_storage(&self) -> bool {
+        self.version_at_least(4, 4) || self.has_extension("GL_ARB_buffer_storage")
+    }
+
+    pub fn supports_multi_draw_indirect(&self) -> bool {
+        self.version_at_least(4, 3) || self.has_extension("GL_ARB_multi_draw_indirect")
+    }
+
+    pub fn supports_sampler_objects(&self) -> bool {
+        self.version_at_least(3, 3) || self.has_extension("GL_ARB_sampler_objects")
+    }
+
+    pub fn supports_direct_state_access(&self) -> bool {
+        self.version_at_least(4, 5) || self.has_extension("GL_ARB_direct_state_access")
+    }
+
+    // Logs a summary of the context, a more detailed
+    // version of `gl_info()`
+    pub fn log(&self) {
+        info!("[elara-gfx] OpenGL Renderer: {} ({})", self.renderer, self.vendor);
+        info!("[elara-gfx] OpenGL Version: {}.{}{} | GLSL {}", self.gl_version.0, self.gl_version.1, if self.is_gles { " ES" } else { "" }, self.glsl_version_number());
+        info!("[elara-gfx] Max texture size: {} | Max samples: {} | Max vertex attribs: {}", self.max_texture_size, self.max_samples, self.max_vertex_attribs);
+        info!("[elara-gfx] Max uniform block size: {} | Compute shaders: {}", self.max_uniform_block_size, self.supports_compute());
+        info!("[elara-gfx] {} extensions supported", self.extensions.len());
+    }
+}
+
+// Forgets the cached capabilities; called whenever GL functions
+// are loaded for a new context
+pub(crate) fn reset_current() {
+    *CURRENT.write().unwrap_or_else(|err| err.into_inner()) = None;
+}
+
+fn get_integer(pname: types::GLenum) -> i32 {
+    let mut value = 0;
+    unsafe {
+        gl::GetIntegerv(pname, &mut value);
+    }
+    value
+}
+
+fn query_extensions() -> HashSet<String> {
+    // Core profiles removed glGetString(GL_EXTENSIONS), so
+    // go through the indexed query when it is available
+    if gl::GetStringi::is_loaded() {
+        let count = get_integer(gl::NUM_EXTENSIONS);
+        (0..count.max(0) as types::GLuint)
+            .filter_map(|i| unsafe {
+                let s = gl::GetStringi(gl::EXTENSIONS, i);
+                (!s.is_null()).then(|| CStr::from_ptr(s.cast()).to_string_lossy().into_owned())
+            })
+            .collect()
+    } else {
+        gl_get_string(gl::EXTENSIONS)
+            .map(|s| s.split_whitespace().map(String::from).collect())
+            .unwrap_or_default()
+    }
+}
+
+// Finds the first "major.minor" number in strings like
+// "4.5 (Core Profile) Mesa 22.3.6", "OpenGL ES 3.2 NVIDIA"
+// or "OpenGL ES GLSL ES 3.20"
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let number = version
+        .split_whitespace()
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))?;
+    let mut parts = number.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor: String = parts.next()?.chars().take_while(|c| c.is_ascii_digit()).collect();
+    Some((major, minor.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_driver_version_strings() {
+        let cases = [
+            ("4.6.0 NVIDIA 535.183.01", Some((4, 6))),
+            ("OpenGL ES 3.2 Mesa 22.3.6", Some((3, 2))),
+            ("3.3 (Core Profile) Mesa 23.1.0", Some((3, 3))),
+            ("4.5 (Core Profile) Mesa 22.3.6", Some((4, 5))),
+            ("4.6.14761 Compatibility Profile Context 21.30.25.01", Some((4, 6))),
+            ("OpenGL ES 2.0 (ANGLE 2.1.0)", Some((2, 0))),
+            ("4.1 INTEL-18.5.8", Some((4, 1))),
+            ("", None),
+            ("OpenGL ES", None),
+            ("4", None),
+        ];
+        for (version, expected) in cases {
+            assert_eq!(parse_version(version), expected, "{:?}", version);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Compute shaders, shader storage and image load/store
+//!
+//! Requires GL 4.3 or `ARB_compute_shader`. A typical step
+//! binds its inputs and outputs, dispatches and then issues a
+//! barrier for however the results are used next:
+//!
+//! ```ignore
+//! let program = Program::compute(include_str!("shaders/step.comp"))?;
+//! particles.bind_base(BufferType::ShaderStorage, 0);
+//! program.dispatch(particle_count.div_ceil(64) as u32, 1, 1)?;
+//! memory_barrier(MemoryBarrier::VERTEX_ATTRIB_ARRAY);
+//! ```
+use crate::{types, Buffer, BufferType, GfxError, GfxResult, GlCapabilities, Pod, Program, Shader, ShaderStage, Texture2D};
+use elara_log::prelude::*;
+use std::ffi::CString;
+use std::ops::{BitOr, BitOrAssign};
+
+// Arguments of an indirect dispatch, usually written
+// by another compute shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DispatchIndirectCommand {
+    pub num_groups_x: u32,
+    pub num_groups_y: u32,
+    pub num_groups_z: u32,
+}
+
+unsafe impl Pod for DispatchIndirectCommand {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageAccess {
+    ReadOnly = gl::READ_ONLY as isize,
+    WriteOnly = gl::WRITE_ONLY as isize,
+    ReadWrite = gl::READ_WRITE as isize,
+}
+
+// Which kinds of accesses must see the writes shaders made
+// before the barrier; combine them with `|`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MemoryBarrier(pub types::GLbitfield);
+
+impl MemoryBarrier {
+    pub const VERTEX_ATTRIB_ARRAY: MemoryBarrier = MemoryBarrier(gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT);
+    pub const ELEMENT_ARRAY: MemoryBarrier = MemoryBarrier(gl::ELEMENT_ARRAY_BARRIER_BIT);
+    pub const UNIFORM: MemoryBarrier = MemoryBarrier(gl::UNIFORM_BARRIER_BIT);
+    pub const TEXTURE_FETCH: MemoryBarrier = MemoryBarrier(gl::TEXTURE_FETCH_BARRIER_BIT);
+    pub const SHADER_IMAGE_ACCESS: MemoryBarrier = MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    pub const COMMAND: MemoryBarrier = MemoryBarrier(gl::COMMAND_BARRIER_BIT);
+    pub const PIXEL_BUFFER: MemoryBarrier = MemoryBarrier(gl::PIXEL_BUFFER_BARRIER_BIT);
+    pub const TEXTURE_UPDATE: MemoryBarrier = MemoryBarrier(gl::TEXTURE_UPDATE_BARRIER_BIT);
+    pub const BUFFER_UPDATE: MemoryBarrier = MemoryBarrier(gl::BUFFER_UPDATE_BARRIER_BIT);
+    pub const FRAMEBUFFER: MemoryBarrier = MemoryBarrier(gl::FRAMEBUFFER_BARRIER_BIT);
+    pub const SHADER_STORAGE: MemoryBarrier = MemoryBarrier(gl::SHADER_STORAGE_BARRIER_BIT);
+    pub const ALL: MemoryBarrier = MemoryBarrier(gl::ALL_BARRIER_BITS);
+}
+
+impl BitOr for MemoryBarrier {
+    type Output = MemoryBarrier;
+
+    fn bitor(self, other: MemoryBarrier) -> MemoryBarrier {
+        MemoryBarrier(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for MemoryBarrier {
+    fn bitor_assign(&mut self, other: MemoryBarrier) {
+        self.0 |= other.0;
+    }
+}
+
+// Makes writes from earlier shader invocations (storage
+// buffers, images, atomic counters) visible to `barrier`
+pub fn memory_barrier(barrier: MemoryBarrier) {
+    unsafe {
+        gl::MemoryBarrier(barrier.0);
+    }
+}
+
+fn check_compute_support() -> GfxResult<()> {
+    // The entry point can be loaded even when the context
+    // lacks compute shaders, so ask the context itself
+    if GlCapabilities::query().supports_compute() {
+        Ok(())
+    } else {
+        Err(GfxError::Unsupported("compute shaders"))
+    }
+}
+
+impl Program {
+    // Compiles and links a program made of a single compute
+    // shader; the only place support is checked, dispatching
+    // runs every frame
+    pub fn compute(source: &str) -> GfxResult<Program> {
+        check_compute_support()?;
+        Program::new(&[Shader::new(source, ShaderStage::Compute)?])
+    }
+
+    // The `local_size_*` the compute shader was declared with
+    pub fn work_group_size(&self) -> [u32; 3] {
+        let mut size = [0; 3];
+        unsafe {
+            gl::GetProgramiv(self.id(), gl::COMPUTE_WORK_GROUP_SIZE, size.as_mut_ptr());
+        }
+        size.map(|s| s as u32)
+    }
+
+    // Uses the program and launches `x * y * z` work groups; the
+    // program must come from compute(), which checked support
+    pub fn dispatch(&self, x: u32, y: u32, z: u32) -> GfxResult<()> {
+        self.use_program();
+        unsafe {
+            gl::DispatchCompute(x, y, z);
+        }
+        Ok(())
+    }
+
+    // Like dispatch(), but reads the group counts from the
+    // command at `index` in `commands` when the GPU executes it
+    pub fn dispatch_indirect(&self, commands: &Buffer<DispatchIndirectCommand>, index: usize) -> GfxResult<()> {
+        if index >= commands.len() {
+            return Err(GfxError::BufferOutOfBounds { offset: index, len: 1, capacity: commands.len() });
+        }
+        self.use_program();
+        commands.bind(BufferType::DispatchIndirect);
+        unsafe {
+            gl::DispatchComputeIndirect((index * std::mem::size_of::<DispatchIndirectCommand>()) as types::GLintptr);
+        }
+        commands.unbind(BufferType::DispatchIndirect);
+        Ok(())
+    }
+
+    // Assigns shader storage block `name` to binding point
+    // `binding`, for shaders without `layout(binding = N)`;
+    // like `set()`, a block that is not active only logs a warning
+    pub fn set_storage_block_binding(&self, name: &str, binding: u32) {
+        let index = CString::new(name)
+            .map(|cname| unsafe { gl::GetProgramResourceIndex(self.id(), gl::SHADER_STORAGE_BLOCK, cname.as_ptr()) })
+            .unwrap_or(gl::INVALID_INDEX);
+        if index == gl::INVALID_INDEX {
+            warn!("[elara-gfx] Shader storage block `{}` is not active in program {}", name, self.id());
+            return;
+        }
+        unsafe {
+            gl::ShaderStorageBlockBinding(self.id(), index, binding);
+        }
+    }
+}
+
+impl Texture2D {
+    // Binds mip `level` to image unit `unit` for imageLoad()/
+    // imageStore(); `format` is the sized format the shader
+    // declares, e.g. `gl::RGBA8` for `layout(rgba8)`
+    pub fn bind_image(&self, unit: u32, level: i32, access: ImageAccess, format: types::GLenum) {
+        unsafe {
+            gl::BindImageTexture(unit, self.id(), level, gl::FALSE, 0, access as types::GLenum, format);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! OpenGL debug output (KHR_debug) routed into elara-log
+//!
+//! Debug output is opt-in: enable it with `WindowOptions::debug()`,
+//! `HeadlessContext::new_debug()` or by calling
+//! `enable_debug_output()` once a context is current.
+use crate::capabilities::GlCapabilities;
+use crate::types;
+use elara_log::prelude::*;
+use std::ffi::CStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DEBUG_ENABLED: AtomicBool = AtomicBool::new(false);
+
+// Registers a debug message callback that forwards driver
+// messages to elara-log, returns false if the context does
+// not support KHR_debug
+pub fn enable_debug_output() -> bool {
+    if !GlCapabilities::current().supports_debug_output() {
+        warn!("[elara-gfx] KHR_debug is not supported by this context, debug output disabled");
+        return false;
+    }
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        // Report messages from inside the offending GL call
+        // so they show up next to the code that caused them
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(Some(debug_callback), std::ptr::null());
+        gl::DebugMessageControl(gl::DONT_CARE, gl::DONT_CARE, gl::DONT_CARE, 0, std::ptr::null(), gl::TRUE);
+    }
+    DEBUG_ENABLED.store(true, Ordering::Relaxed);
+    info!("[elara-gfx] OpenGL debug output enabled");
+    true
+}
+
+pub fn is_debug_enabled() -> bool {
+    DEBUG_ENABLED.load(Ordering::Relaxed)
+}
+
+extern "system" fn debug_callback(
+    source: types::GLenum,
+    gltype: types::GLenum,
+    id: types::GLuint,
+    severity: types::GLenum,
+    length: types::GLsizei,
+    message: *const types::GLchar,
+    _user_param: *mut types::c_void,
+) {
+    if message.is_null() {
+        return;
+    }
+    // Messages for debug groups are not always null-terminated,
+    // so prefer the length reported by the driver
+    let message = if length >= 0 {
+        let bytes = unsafe { std::slice::from_raw_parts(message as *const u8, length as usize) };
+        String::from_utf8_lossy(bytes)
+    } else {
+        unsafe { CStr::from_ptr(message) }.to_string_lossy()
+    };
+    let source = source_name(source);
+    let gltype = type_name(gltype);
+    match severity {
+        gl::DEBUG_SEVERITY_HIGH => error!("[elara-gfx] GL {} {} ({}): {}", source, gltype, id, message),
+        gl::DEBUG_SEVERITY_MEDIUM => warn!("[elara-gfx] GL {} {} ({}): {}", source, gltype, id, message),
+        gl::DEBUG_SEVERITY_LOW => info!("[elara-gfx] GL {} {} ({}): {}", source, gltype, id, message),
+        _ => debug!("[elara-gfx] GL {} {} ({}): {}", source, gltype, id, message),
+    }
+}
+
+fn source_name(source: types::GLenum) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API => "API",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    }
+}
+
+fn type_name(gltype: types::GLenum) -> &'static str {
+    match gltype {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behavior",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        gl::DEBUG_TYPE_PUSH_GROUP => "push group",
+        gl::DEBUG_TYPE_POP_GROUP => "pop group",
+        _ => "other",
+    }
+}
+
+// Attaches a human-readable name to a GL object so that
+// debug messages mention it; GL requires the object to have
+// been bound at least once before it can be labelled
+pub(crate) fn object_label(identifier: types::GLenum, id: types::GLuint, label: &str) {
+    if !GlCapabilities::current().supports_debug_output() {
+        return;
+    }
+    unsafe {
+        gl::ObjectLabel(identifier, id, label.len() as types::GLsizei, label.as_ptr().cast());
+    }
+}
+
+// Groups all GL calls made while it is alive under `name`
+// in debug output and in tools like RenderDoc and apitrace;
+// does nothing unless debug output is enabled
+pub struct DebugGroup {
+    active: bool,
+}
+
+impl DebugGroup {
+    pub fn new(name: &str) -> DebugGroup {
+        // Debug output is only ever enabled on contexts that support KHR_debug
+        let active = is_debug_enabled();
+        if active {
+            unsafe {
+                gl::PushDebugGroup(gl::DEBUG_SOURCE_APPLICATION, 0, name.len() as types::GLsizei, name.as_ptr().cast());
+            }
+        }
+        DebugGroup { active }
+    }
+}
+
+impl Drop for DebugGroup {
+    fn drop(&mut self) {
+        if self.active {
+            unsafe {
+                gl::PopDebugGroup();
+            }
+        }
+    }
+}
@@ -0,0 +1,287 @@
+//! Structured diagnostics parsed from shader info logs
+//!
+//! Drivers format their compile and link logs differently;
+//! `parse_info_log()` understands the Mesa, NVIDIA and
+//! AMD/Intel formats and turns each message into a
+//! `ShaderDiagnostic` that renders with its source line:
+//!
+//! ```text
+//! error: `x' undeclared
+//!  --> particles.frag:4:23
+//!   |
+//! 3 | out vec4 f;
+//! 4 | void main(){ f = vec4(x); }
+//!   |                       ^
+//! ```
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+    pub severity: Severity,
+    // Set when the source went through `ShaderSource`
+    pub file: Option<String>,
+    // 1-based, `None` for messages about the whole shader or
+    // program, like most link errors
+    pub line: Option<usize>,
+    // 1-based, only some drivers report it
+    pub column: Option<usize>,
+    pub message: String,
+    // The offending line and the one before it, as
+    // (line number, text)
+    pub excerpt: Vec<(usize, String)>,
+}
+
+impl ShaderDiagnostic {
+    // Fills in the excerpt from the source the log refers to
+    pub(crate) fn attach_excerpt(&mut self, source: &str) {
+        self.excerpt.clear();
+        if let Some(line) = self.line.filter(|&line| line > 0) {
+            let first = line.saturating_sub(1).max(1);
+            self.excerpt = source
+                .lines()
+                .enumerate()
+                .map(|(i, text)| (i + 1, text))
+                .filter(|&(number, _)| number >= first && number <= line)
+                .map(|(number, text)| (number, text.to_string()))
+                .collect();
+        }
+    }
+}
+
+impl fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.severity.name(), self.message)?;
+        let line = match self.line {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+        match (&self.file, self.column) {
+            (Some(file), Some(column)) => write!(f, "\n --> {}:{}:{}", file, line, column)?,
+            (Some(file), None) => write!(f, "\n --> {}:{}", file, line)?,
+            (None, Some(column)) => write!(f, "\n --> line {}, column {}", line, column)?,
+            (None, None) => write!(f, "\n --> line {}", line)?,
+        }
+        if self.excerpt.is_empty() {
+            return Ok(());
+        }
+        let width = self.excerpt.iter().map(|(number, _)| number.to_string().len()).max().unwrap_or(1);
+        write!(f, "\n{:width$} |", "", width = width)?;
+        for (number, text) in &self.excerpt {
+            write!(f, "\n{:>width$} | {}", number, text, width = width)?;
+        }
+        if let (Some(column), Some((number, text))) = (self.column, self.excerpt.last()) {
+            if *number == line && column > 0 {
+                // Keep tabs so the caret lines up with the source
+                let indent: String = text.chars().take(column - 1).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+                write!(f, "\n{:width$} | {}^", "", indent, width = width)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Parses every message in a compile or link log; lines that
+// don't start a new message are appended to the previous one
+pub fn parse_info_log(log: &str) -> Vec<ShaderDiagnostic> {
+    let mut diagnostics: Vec<ShaderDiagnostic> = Vec::new();
+    for line in log.lines().map(str::trim_end).filter(|line| !line.trim().is_empty()) {
+        match parse_line(line) {
+            Some(diagnostic) => {
+                // AMD ends its logs with a summary of the errors
+                if diagnostic.line.is_none() && diagnostic.message.ends_with("No code generated.") {
+                    continue;
+                }
+                diagnostics.push(diagnostic);
+            }
+            None => match diagnostics.last_mut() {
+                Some(last) => {
+                    last.message.push('\n');
+                    last.message.push_str(line.trim());
+                }
+                None => diagnostics.push(ShaderDiagnostic {
+                    severity: Severity::Error,
+                    file: None,
+                    line: None,
+                    column: None,
+                    message: line.trim().to_string(),
+                    excerpt: Vec::new(),
+                }),
+            },
+        }
+    }
+    diagnostics
+}
+
+fn parse_line(line: &str) -> Option<ShaderDiagnostic> {
+    // AMD and Intel put the severity first
+    let mut severity = [("ERROR: ", Severity::Error), ("WARNING: ", Severity::Warning)]
+        .iter()
+        .find(|(prefix, _)| line.starts_with(prefix))
+        .map(|&(_, severity)| severity);
+
+    let (location, mut rest) = match parse_log_location(line) {
+        Some(location) => (Some(location), &line[location.end..]),
+        None => (None, line.strip_prefix("ERROR: ").or_else(|| line.strip_prefix("WARNING: ")).unwrap_or(line)),
+    };
+
+    // Mesa follows the line with `(column)`
+    let mut column = None;
+    if let Some(after) = rest.strip_prefix('(') {
+        if let Some((number, after)) = after.split_once(')') {
+            column = number.parse().ok();
+            rest = after;
+        }
+    }
+    rest = rest.trim_start_matches(|c: char| c == ':' || c.is_whitespace());
+
+    // Mesa and NVIDIA put it after the location
+    if severity.is_none() {
+        let lower = rest.to_ascii_lowercase();
+        for (word, word_severity) in [("error", Severity::Error), ("warning", Severity::Warning), ("info", Severity::Info)] {
+            if lower.starts_with(word) {
+                severity = Some(word_severity);
+                rest = rest[word.len()..].trim_start_matches(|c: char| c == ':' || c.is_whitespace());
+                break;
+            }
+        }
+    }
+
+    // Without a location or a severity it's a continuation
+    if location.is_none() && severity.is_none() {
+        return None;
+    }
+    Some(ShaderDiagnostic {
+        severity: severity.unwrap_or(Severity::Error),
+        file: None,
+        line: location.map(|location| location.line),
+        column,
+        message: rest.to_string(),
+        excerpt: Vec::new(),
+    })
+}
+
+// Where a location such as `0:12` sits in a line of a log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LogLocation {
+    // Byte range of the source number and line
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    // `0(12)` rather than `0:12`
+    pub parenthesized: bool,
+}
+
+impl LogLocation {
+    pub(crate) fn format_line(&self, line: usize) -> String {
+        if self.parenthesized {
+            format!("({})", line)
+        } else {
+            format!(":{}", line)
+        }
+    }
+}
+
+// Finds the location at the start of an info log line in the
+// formats used by Mesa (`0:12(5): error: ...`), NVIDIA
+// (`0(12) : error C0000: ...`) and AMD/Intel on Windows
+// (`ERROR: 0:12: ...`)
+pub(crate) fn parse_log_location(line: &str) -> Option<LogLocation> {
+    let start = ["ERROR: ", "WARNING: "]
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix).map(|_| prefix.len()))
+        .unwrap_or(0);
+    let rest = &line[start..];
+    let source_len = rest.find(|c: char| !c.is_ascii_digit())?;
+    if source_len == 0 {
+        return None;
+    }
+    let (parenthesized, after) = match rest[source_len..].chars().next()? {
+        ':' => (false, &rest[source_len + 1..]),
+        '(' => (true, &rest[source_len + 1..]),
+        _ => return None,
+    };
+    let line_len = after.find(|c: char| !c.is_ascii_digit()).unwrap_or(after.len());
+    let number = after[..line_len].parse().ok()?;
+    let mut end = start + source_len + 1 + line_len;
+    if parenthesized {
+        if !after[line_len..].starts_with(')') {
+            return None;
+        }
+        end += 1;
+    }
+    Some(LogLocation { start, end, line: number, parenthesized })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mesa_line() {
+        let diagnostics = parse_info_log("0:4(23): error: `x' undeclared\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].line, Some(4));
+        assert_eq!(diagnostics[0].column, Some(23));
+        assert_eq!(diagnostics[0].message, "`x' undeclared");
+    }
+
+    #[test]
+    fn parses_nvidia_line() {
+        let diagnostics = parse_info_log("0(3) : warning C7555: 'varying' is deprecated, use 'in/out' instead\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].line, Some(3));
+        assert_eq!(diagnostics[0].column, None);
+        assert_eq!(diagnostics[0].message, "C7555: 'varying' is deprecated, use 'in/out' instead");
+    }
+
+    #[test]
+    fn parses_amd_line_and_skips_summary() {
+        let log = "ERROR: 0:7: 'undefined_fn' : no matching overloaded function found\nERROR: 1 compilation errors.  No code generated.\n";
+        let diagnostics = parse_info_log(log);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].line, Some(7));
+        assert_eq!(diagnostics[0].column, None);
+        assert_eq!(diagnostics[0].message, "'undefined_fn' : no matching overloaded function found");
+    }
+
+    #[test]
+    fn appends_continuation_lines() {
+        let log = "0:3(1): error: syntax error, unexpected '}'\n  expected ';' before '}'\n0:5(2): warning: unused variable\n";
+        let diagnostics = parse_info_log(log);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].message, "syntax error, unexpected '}'\nexpected ';' before '}'");
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+        assert_eq!(diagnostics[1].line, Some(5));
+    }
+
+    #[test]
+    fn finds_log_locations() {
+        let mesa = parse_log_location("0:12(5): error: x").unwrap();
+        assert_eq!((mesa.start, mesa.end, mesa.line, mesa.parenthesized), (0, 4, 12, false));
+        let nvidia = parse_log_location("0(12) : error C0000: x").unwrap();
+        assert_eq!((nvidia.start, nvidia.end, nvidia.line, nvidia.parenthesized), (0, 5, 12, true));
+        let amd = parse_log_location("ERROR: 0:12: x").unwrap();
+        assert_eq!((amd.start, amd.end, amd.line), (7, 11, 12));
+        assert_eq!(parse_log_location("error: linking failed"), None);
+    }
+}
@@ -0,0 +1,240 @@
+//! Draw calls on vertex arrays
+//!
+//! Every draw binds the vertex array it is called on and
+//! uses whichever program is currently in use.
+use crate::{types, Buffer, BufferType, GfxError, GfxResult, Program, VertexArray, VertexLayout};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PrimitiveMode {
+    Points = gl::POINTS as isize,
+    Lines = gl::LINES as isize,
+    LineStrip = gl::LINE_STRIP as isize,
+    LineLoop = gl::LINE_LOOP as isize,
+    #[default]
+    Triangles = gl::TRIANGLES as isize,
+    TriangleStrip = gl::TRIANGLE_STRIP as isize,
+    TriangleFan = gl::TRIANGLE_FAN as isize,
+    LinesAdjacency = gl::LINES_ADJACENCY as isize,
+    LineStripAdjacency = gl::LINE_STRIP_ADJACENCY as isize,
+    TrianglesAdjacency = gl::TRIANGLES_ADJACENCY as isize,
+    TriangleStripAdjacency = gl::TRIANGLE_STRIP_ADJACENCY as isize,
+    // Requires a tessellation control or evaluation shader
+    Patches = gl::PATCHES as isize,
+}
+
+// Number of vertices per patch for `PrimitiveMode::Patches`,
+// 3 by default
+pub fn set_patch_vertices(count: u32) {
+    unsafe {
+        gl::PatchParameteri(gl::PATCH_VERTICES, count as types::GLint);
+    }
+}
+
+// Blends what is drawn over the framebuffer by its alpha;
+// this is global state, not tied to any texture
+pub fn enable_alpha_blend() {
+    unsafe {
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+    }
+}
+
+// Element types that can be used in an index buffer
+pub trait IndexType: Copy {
+    const GL_TYPE: types::GLenum;
+}
+
+impl IndexType for u8 {
+    const GL_TYPE: types::GLenum = gl::UNSIGNED_BYTE;
+}
+
+impl IndexType for u16 {
+    const GL_TYPE: types::GLenum = gl::UNSIGNED_SHORT;
+}
+
+impl IndexType for u32 {
+    const GL_TYPE: types::GLenum = gl::UNSIGNED_INT;
+}
+
+// A range of indices (or vertices) for multi-draw calls
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DrawRange {
+    pub first: usize,
+    pub count: usize,
+    // Added to every index, ignored by non-indexed draws
+    pub base_vertex: i32,
+}
+
+impl DrawRange {
+    pub fn new(first: usize, count: usize) -> DrawRange {
+        DrawRange { first, count, base_vertex: 0 }
+    }
+
+    pub fn base_vertex(mut self, base_vertex: i32) -> Self {
+        self.base_vertex = base_vertex;
+        self
+    }
+}
+
+fn index_offset<I: IndexType>(first: usize) -> *const types::c_void {
+    (first * std::mem::size_of::<I>()) as *const types::c_void
+}
+
+fn check_indices<I: IndexType>(indices: &Buffer<I>, first: usize, count: usize) -> GfxResult<()> {
+    match first.checked_add(count) {
+        Some(end) if end <= indices.len() => Ok(()),
+        _ => Err(GfxError::BufferOutOfBounds { offset: first, len: count, capacity: indices.len() }),
+    }
+}
+
+impl VertexArray {
+    // Adds per-instance attributes read from `buffer`, which
+    // advance once every `divisor` instances instead of once
+    // per vertex
+    pub fn add_instance_buffer<V: VertexLayout>(&self, program: &Program, buffer: &Buffer<impl Copy>, divisor: u32) {
+        self.bind();
+        buffer.bind(BufferType::Array);
+        for index in self.set_layout::<V>(program) {
+            self.vertex_attrib_divisor(index, divisor);
+        }
+        self.unbind();
+        buffer.unbind(BufferType::Array);
+    }
+
+    pub fn vertex_attrib_divisor(&self, index: types::GLuint, divisor: u32) {
+        unsafe {
+            gl::VertexAttribDivisor(index, divisor);
+        }
+    }
+
+    pub fn draw_arrays(&self, mode: PrimitiveMode, first: usize, count: usize) {
+        self.bind();
+        unsafe {
+            gl::DrawArrays(mode as types::GLenum, first as types::GLint, count as types::GLsizei);
+        }
+    }
+
+    pub fn draw_arrays_instanced(&self, mode: PrimitiveMode, first: usize, count: usize, instances: usize) {
+        self.bind();
+        unsafe {
+            gl::DrawArraysInstanced(mode as types::GLenum, first as types::GLint, count as types::GLsizei, instances as types::GLsizei);
+        }
+    }
+
+    // Draws `count` indices starting at index `first`; `indices`
+    // becomes the element buffer of this vertex array
+    pub fn draw_elements<I: IndexType>(&self, mode: PrimitiveMode, indices: &Buffer<I>, first: usize, count: usize) -> GfxResult<()> {
+        check_indices(indices, first, count)?;
+        self.bind();
+        indices.bind(BufferType::ElementArray);
+        unsafe {
+            gl::DrawElements(mode as types::GLenum, count as types::GLsizei, I::GL_TYPE, index_offset::<I>(first));
+        }
+        Ok(())
+    }
+
+    pub fn draw_elements_instanced<I: IndexType>(
+        &self,
+        mode: PrimitiveMode,
+        indices: &Buffer<I>,
+        first: usize,
+        count: usize,
+        instances: usize,
+    ) -> GfxResult<()> {
+        check_indices(indices, first, count)?;
+        self.bind();
+        indices.bind(BufferType::ElementArray);
+        unsafe {
+            gl::DrawElementsInstanced(
+                mode as types::GLenum,
+                count as types::GLsizei,
+                I::GL_TYPE,
+                index_offset::<I>(first),
+                instances as types::GLsizei,
+            );
+        }
+        Ok(())
+    }
+
+    // Like draw_elements(), but adds `base_vertex` to every index
+    // so several meshes can share one vertex and index buffer
+    pub fn draw_elements_base_vertex<I: IndexType>(
+        &self,
+        mode: PrimitiveMode,
+        indices: &Buffer<I>,
+        first: usize,
+        count: usize,
+        base_vertex: i32,
+    ) -> GfxResult<()> {
+        check_indices(indices, first, count)?;
+        self.bind();
+        indices.bind(BufferType::ElementArray);
+        unsafe {
+            gl::DrawElementsBaseVertex(
+                mode as types::GLenum,
+                count as types::GLsizei,
+                I::GL_TYPE,
+                index_offset::<I>(first),
+                base_vertex,
+            );
+        }
+        Ok(())
+    }
+
+    pub fn draw_elements_instanced_base_vertex<I: IndexType>(
+        &self,
+        mode: PrimitiveMode,
+        indices: &Buffer<I>,
+        range: DrawRange,
+        instances: usize,
+    ) -> GfxResult<()> {
+        check_indices(indices, range.first, range.count)?;
+        self.bind();
+        indices.bind(BufferType::ElementArray);
+        unsafe {
+            gl::DrawElementsInstancedBaseVertex(
+                mode as types::GLenum,
+                range.count as types::GLsizei,
+                I::GL_TYPE,
+                index_offset::<I>(range.first),
+                instances as types::GLsizei,
+                range.base_vertex,
+            );
+        }
+        Ok(())
+    }
+
+    // Issues one draw per range in a single call
+    pub fn multi_draw_arrays(&self, mode: PrimitiveMode, ranges: &[DrawRange]) {
+        let firsts: Vec<types::GLint> = ranges.iter().map(|r| r.first as types::GLint).collect();
+        let counts: Vec<types::GLsizei> = ranges.iter().map(|r| r.count as types::GLsizei).collect();
+        self.bind();
+        unsafe {
+            gl::MultiDrawArrays(mode as types::GLenum, firsts.as_ptr(), counts.as_ptr(), ranges.len() as types::GLsizei);
+        }
+    }
+
+    // Issues one indexed draw per range in a single call,
+    // honouring each range's base vertex
+    pub fn multi_draw_elements<I: IndexType>(&self, mode: PrimitiveMode, indices: &Buffer<I>, ranges: &[DrawRange]) -> GfxResult<()> {
+        for range in ranges {
+            check_indices(indices, range.first, range.count)?;
+        }
+        let counts: Vec<types::GLsizei> = ranges.iter().map(|r| r.count as types::GLsizei).collect();
+        let offsets: Vec<*const types::c_void> = ranges.iter().map(|r| index_offset::<I>(r.first)).collect();
+        let base_vertices: Vec<types::GLint> = ranges.iter().map(|r| r.base_vertex).collect();
+        self.bind();
+        indices.bind(BufferType::ElementArray);
+        unsafe {
+            gl::MultiDrawElementsBaseVertex(
+                mode as types::GLenum,
+                counts.as_ptr(),
+                I::GL_TYPE,
+                offsets.as_ptr(),
+                ranges.len() as types::GLsizei,
+                base_vertices.as_ptr(),
+            );
+        }
+        Ok(())
+    }
+}
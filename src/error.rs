@@ -1,36 +1,242 @@
 //! All error types for elara-gfx
 use std::error::Error;
 use std::fmt;
+use khronos_egl as egl;
+use raw_gl_context::GlError as RawGlError;
+use winit::error::OsError;
+use crate::image::ImageError;
+use crate::types::GLenum;
+use crate::{ShaderDiagnostic, ShaderStage};
 
 // pub type WindowResult<T> = Result<T, WindowError>;
 
 #[derive(Debug)]
 pub enum WindowError {
-    WindowCreationError,
+    WindowCreationError(OsError),
+    ContextCreationError(RawGlError),
+    EglLoadError(Box<dyn Error + Send + Sync>),
+    EglError(egl::Error),
     PlatformError,
+    // Offscreen surfaces need a positive width and height
+    InvalidSize { width: i32, height: i32 },
+    // The framebuffer a headless context renders into
+    // could not be created or is incomplete
+    FramebufferError(GfxError),
 }
 
-// TODO: proper Error trait implementation
-impl Error for WindowError {}
+impl Error for WindowError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            WindowError::WindowCreationError(ref err) => Some(err),
+            WindowError::EglLoadError(ref err) => Some(err.as_ref()),
+            WindowError::EglError(ref err) => Some(err),
+            // raw-gl-context errors don't implement `Error`
+            WindowError::ContextCreationError(_) => None,
+            WindowError::PlatformError => None,
+            WindowError::InvalidSize { .. } => None,
+            WindowError::FramebufferError(ref err) => Some(err),
+        }
+    }
+}
 
-// TODO: proper error implementation for `WindowError`
 impl fmt::Display for WindowError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "An error occurred during window creation.")
+        match *self {
+            WindowError::WindowCreationError(ref err) => write!(f, "Window creation failed: {}", err),
+            WindowError::ContextCreationError(ref err) => write!(f, "OpenGL context creation failed: {:?}", err),
+            WindowError::EglLoadError(ref err) => write!(f, "Failed to load libEGL: {}", err),
+            WindowError::EglError(ref err) => write!(f, "EGL error: {}", err),
+            WindowError::PlatformError => write!(f, "The platform does not support the requested OpenGL configuration"),
+            WindowError::InvalidSize { width, height } => write!(f, "Invalid surface size {}x{}", width, height),
+            WindowError::FramebufferError(ref err) => write!(f, "Offscreen framebuffer setup failed: {}", err),
+        }
+    }
+}
+
+impl From<OsError> for WindowError {
+    fn from(err: OsError) -> WindowError {
+        WindowError::WindowCreationError(err)
+    }
+}
+
+impl From<RawGlError> for WindowError {
+    fn from(err: RawGlError) -> WindowError {
+        WindowError::ContextCreationError(err)
+    }
+}
+
+impl From<egl::Error> for WindowError {
+    fn from(err: egl::Error) -> WindowError {
+        WindowError::EglError(err)
     }
 }
 
-pub type GlResult<T> = Result<T, GlError>;
+pub type GfxResult<T> = Result<T, GfxError>;
 
 #[derive(Debug)]
-pub struct GlError;
+pub enum GfxError {
+    // `diagnostics` holds the parsed messages of `log`, with the
+    // source lines they point at
+    ShaderCompileError { stage: ShaderStage, log: String, diagnostics: Vec<ShaderDiagnostic> },
+    // `path` could not be included from line `line` of `from`
+    IncludeError { path: String, from: String, line: usize, reason: &'static str },
+    // Why the stages can't be linked into one program
+    InvalidShaderStages(&'static str),
+    ProgramLinkError { log: String, diagnostics: Vec<ShaderDiagnostic> },
+    MissingUniform(String),
+    // The GLSL type of the uniform and the one of the value
+    UniformTypeMismatch { name: String, expected: &'static str, found: &'static str },
+    // Elements left in the uniform array from the element
+    // being set and the number of values given
+    UniformArrayOverflow { name: String, available: usize, found: usize },
+    // Size of the uniform block in the program and of the
+    // buffer, which is too small for it
+    UniformBlockSizeMismatch { name: String, expected: usize, found: usize },
+    MissingAttribute(String),
+    // Name of the OpenGL object type that could not be created
+    ObjectCreationError(&'static str),
+    GlErrorCode(GLenum),
+    IncompleteFramebuffer(GLenum),
+    FontLoadingError(freetype::Error),
+    ImageLoadingError(ImageError),
+    BufferOutOfBounds { offset: usize, len: usize, capacity: usize },
+    BufferMapError,
+    // Why waiting for the GPU to release memory failed
+    FenceWaitError(&'static str),
+    // Pixel data is smaller than the upload needs, in bytes
+    TextureSizeMismatch { expected: usize, found: usize },
+    // Why texture data or a region can't be used
+    InvalidTextureData(&'static str),
+    // Offscreen surfaces need a positive width and height
+    InvalidSize { width: i32, height: i32 },
+    // Frames per second of a `RedrawMode::TargetFps`
+    InvalidFrameRate(f64),
+    // Name of the feature the context lacks
+    Unsupported(&'static str),
+    // Errors raised by user code inside a `WindowHandler`
+    Other(String),
+}
+
+pub(crate) fn gl_error_name(code: GLenum) -> &'static str {
+    match code {
+        gl::INVALID_ENUM => "Invalid enum",
+        gl::INVALID_VALUE => "Invalid value",
+        gl::INVALID_OPERATION => "Invalid operation",
+        gl::STACK_OVERFLOW => "Stack overflow",
+        gl::STACK_UNDERFLOW => "Stack underflow",
+        gl::OUT_OF_MEMORY => "Out of memory",
+        gl::INVALID_FRAMEBUFFER_OPERATION => "Invalid framebuffer operation",
+        _ => "Unknown GL error",
+    }
+}
+
+fn framebuffer_status_name(status: GLenum) -> &'static str {
+    match status {
+        gl::FRAMEBUFFER_UNDEFINED => "default framebuffer does not exist",
+        gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "an attachment is incomplete",
+        gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "no images are attached",
+        gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "a draw buffer has no attachment",
+        gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "the read buffer has no attachment",
+        gl::FRAMEBUFFER_UNSUPPORTED => "the attachment formats are not supported together",
+        gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "attachments have mismatched sample counts",
+        gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => "attachments have mismatched layer targets",
+        _ => "unknown status",
+    }
+}
 
-// TODO: proper Error trait implementation
-impl Error for GlError {}
+impl Error for GfxError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            GfxError::FontLoadingError(ref err) => Some(err),
+            GfxError::ImageLoadingError(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
 
-// TODO: proper error implementation for `GlError`
-impl fmt::Display for GlError {
+impl fmt::Display for GfxError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "An error occurred with OpenGL.")
+        match *self {
+            GfxError::ShaderCompileError { stage, ref log, ref diagnostics } => {
+                write!(f, "Shader compilation failed ({} stage):", stage.name())?;
+                write_diagnostics(f, log, diagnostics)
+            }
+            GfxError::IncludeError { ref path, ref from, line, reason } => {
+                write!(f, "Cannot include `{}` from {}:{}: {}", path, from, line, reason)
+            }
+            GfxError::InvalidShaderStages(reason) => write!(f, "Invalid combination of shader stages: {}", reason),
+            GfxError::ProgramLinkError { ref log, ref diagnostics } => {
+                write!(f, "Program linking failed:")?;
+                write_diagnostics(f, log, diagnostics)
+            }
+            GfxError::MissingUniform(ref name) => write!(f, "Uniform `{}` not found in program", name),
+            GfxError::UniformTypeMismatch { ref name, expected, found } => {
+                write!(f, "Uniform `{}` has type {} but was set with a {} value", name, expected, found)
+            }
+            GfxError::UniformArrayOverflow { ref name, available, found } => {
+                write!(f, "Uniform array `{}` has room for {} values but was set with {}", name, available, found)
+            }
+            GfxError::UniformBlockSizeMismatch { ref name, expected, found } => {
+                write!(f, "Uniform block `{}` is {} bytes but the buffer only holds {} bytes", name, expected, found)
+            }
+            GfxError::MissingAttribute(ref name) => write!(f, "Attribute `{}` not found in program", name),
+            GfxError::ObjectCreationError(object) => write!(f, "{} creation failed", object),
+            GfxError::GlErrorCode(code) => write!(f, "OpenGL error 0x{:X}: {}", code, gl_error_name(code)),
+            GfxError::IncompleteFramebuffer(status) => {
+                write!(f, "Framebuffer is incomplete (0x{:X}): {}", status, framebuffer_status_name(status))
+            }
+            GfxError::FontLoadingError(ref err) => write!(f, "Font loading failed: {}", err),
+            GfxError::ImageLoadingError(ref err) => write!(f, "Image loading failed: {}", err),
+            GfxError::BufferOutOfBounds { offset, len, capacity } => {
+                write!(f, "Buffer access of {} elements at offset {} exceeds its length of {}", len, offset, capacity)
+            }
+            GfxError::BufferMapError => write!(f, "Mapping buffer memory failed"),
+            GfxError::FenceWaitError(reason) => write!(f, "Waiting for the GPU to release a buffer {}", reason),
+            GfxError::TextureSizeMismatch { expected, found } => {
+                write!(f, "Texture upload needs {} bytes of pixel data but got {}", expected, found)
+            }
+            GfxError::InvalidTextureData(reason) => write!(f, "Invalid texture data: {}", reason),
+            GfxError::InvalidSize { width, height } => write!(f, "Invalid surface size {}x{}", width, height),
+            GfxError::InvalidFrameRate(fps) => write!(f, "Target frame rate must be positive and finite, got {}", fps),
+            GfxError::Unsupported(feature) => write!(f, "The OpenGL context does not support {}", feature),
+            GfxError::Other(ref msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<freetype::Error> for GfxError {
+    fn from(err: freetype::Error) -> GfxError {
+        GfxError::FontLoadingError(err)
+    }
+}
+
+// The parsed diagnostics one per paragraph, or the raw log
+// if none of it could be parsed
+fn write_diagnostics(f: &mut fmt::Formatter, log: &str, diagnostics: &[ShaderDiagnostic]) -> fmt::Result {
+    if diagnostics.is_empty() {
+        return write!(f, " {}", log.trim_end());
+    }
+    for (i, diagnostic) in diagnostics.iter().enumerate() {
+        let separator = if i == 0 { "\n" } else { "\n\n" };
+        write!(f, "{}{}", separator, diagnostic)?;
+    }
+    Ok(())
+}
+
+impl From<ImageError> for GfxError {
+    fn from(err: ImageError) -> GfxError {
+        GfxError::ImageLoadingError(err)
+    }
+}
+
+impl From<String> for GfxError {
+    fn from(msg: String) -> GfxError {
+        GfxError::Other(msg)
+    }
+}
+
+impl From<&str> for GfxError {
+    fn from(msg: &str) -> GfxError {
+        GfxError::Other(msg.to_string())
     }
 }
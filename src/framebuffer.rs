@@ -0,0 +1,398 @@
+//! Framebuffers for rendering into textures and renderbuffers
+//!
+//! Attach color targets and a depth/stencil target, check that
+//! the combination is complete, then render inside a scoped
+//! binding that puts the previous framebuffer and viewport back
+//! when it ends:
+//!
+//! ```ignore
+//! let color = Texture2D::new()?;
+//! color.allocate(TextureFormat::Rgba16F, 512, 512, 1)?;
+//! let depth = RenderBuffer::new()?;
+//! depth.set_storage(TextureFormat::Depth24Stencil8, 512, 512);
+//!
+//! let target = FrameBuffer::new()?;
+//! target.attach_texture(Attachment::Color(0), &color, 0);
+//! target.attach_renderbuffer(Attachment::DepthStencil, &depth);
+//! target.check_status()?;
+//! {
+//!     let _binding = target.bind_scoped();
+//!     // draw the scene
+//! }
+//! ```
+//!
+//! Multisampled rendering goes to renderbuffers created with
+//! `set_storage_multisample()` and is resolved into a regular
+//! framebuffer with `resolve_to()`.
+use crate::debug::object_label;
+use crate::sampler::Filter;
+use crate::{types, GfxError, GfxResult, Texture2D, TextureFormat};
+use std::cell::RefCell;
+use std::ops::{BitOr, BitOrAssign};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attachment {
+    // COLOR_ATTACHMENTi, written by fragment output `i`
+    Color(u32),
+    Depth,
+    Stencil,
+    DepthStencil,
+}
+
+impl Attachment {
+    pub fn to_gl(&self) -> types::GLenum {
+        match *self {
+            Attachment::Color(index) => gl::COLOR_ATTACHMENT0 + index,
+            Attachment::Depth => gl::DEPTH_ATTACHMENT,
+            Attachment::Stencil => gl::STENCIL_ATTACHMENT,
+            Attachment::DepthStencil => gl::DEPTH_STENCIL_ATTACHMENT,
+        }
+    }
+}
+
+// Which buffers a blit copies; combine them with `|`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlitBuffers(pub types::GLbitfield);
+
+impl BlitBuffers {
+    pub const COLOR: BlitBuffers = BlitBuffers(gl::COLOR_BUFFER_BIT);
+    pub const DEPTH: BlitBuffers = BlitBuffers(gl::DEPTH_BUFFER_BIT);
+    pub const STENCIL: BlitBuffers = BlitBuffers(gl::STENCIL_BUFFER_BIT);
+}
+
+impl BitOr for BlitBuffers {
+    type Output = BlitBuffers;
+
+    fn bitor(self, other: BlitBuffers) -> BlitBuffers {
+        BlitBuffers(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for BlitBuffers {
+    fn bitor_assign(&mut self, other: BlitBuffers) {
+        self.0 |= other.0;
+    }
+}
+
+#[derive(Debug)]
+pub struct FrameBuffer {
+    id: types::GLuint,
+    // Every attachment with the size of its image, for the
+    // viewport of scoped bindings and for resolving
+    attachments: RefCell<Vec<(Attachment, (i32, i32))>>,
+}
+
+impl FrameBuffer {
+    pub fn new() -> GfxResult<FrameBuffer> {
+        let mut framebuffer = 0;
+        unsafe {
+            gl::GenFramebuffers(1, &mut framebuffer);
+        }
+        if framebuffer != 0 {
+            Ok(FrameBuffer { id: framebuffer, attachments: RefCell::new(Vec::new()) })
+        } else {
+            Err(GfxError::ObjectCreationError("Framebuffer"))
+        }
+    }
+
+    pub fn id(&self) -> types::GLuint {
+        self.id
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+        }
+    }
+
+    // Binds the framebuffer for drawing and reading and sets the
+    // viewport to its size; both are restored when the returned
+    // binding is dropped
+    pub fn bind_scoped(&self) -> FrameBufferBinding {
+        let binding = FrameBufferBinding::save();
+        self.bind();
+        if let Some((width, height)) = self.size() {
+            unsafe {
+                gl::Viewport(0, 0, width, height);
+            }
+        }
+        binding
+    }
+
+    pub fn set_label(&self, label: &str) {
+        object_label(gl::FRAMEBUFFER, self.id, label);
+    }
+
+    // Attaches `render_buffer` as color attachment 0 of the
+    // bound draw framebuffer
+    pub fn set_renderbuffer(&self, render_buffer: &RenderBuffer) {
+        unsafe {
+            gl::FramebufferRenderbuffer(
+                gl::DRAW_FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::RENDERBUFFER,
+                render_buffer.id()
+            )
+        }
+    }
+
+    pub fn set_color_buffer(&self) {
+        unsafe {
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+        }
+    }
+
+    // Attaches mip `level` of `texture`; color attachments are
+    // all drawn to, in the order of their indices
+    pub fn attach_texture(&self, attachment: Attachment, texture: &Texture2D, level: i32) {
+        let (width, height) = texture.size();
+        self.with_bound(|| unsafe {
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment.to_gl(), gl::TEXTURE_2D, texture.id(), level);
+        });
+        self.attached(attachment, ((width >> level).max(1), (height >> level).max(1)));
+    }
+
+    pub fn attach_renderbuffer(&self, attachment: Attachment, render_buffer: &RenderBuffer) {
+        self.with_bound(|| unsafe {
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment.to_gl(), gl::RENDERBUFFER, render_buffer.id());
+        });
+        self.attached(attachment, render_buffer.size());
+    }
+
+    fn attached(&self, attachment: Attachment, size: (i32, i32)) {
+        {
+            let mut attachments = self.attachments.borrow_mut();
+            attachments.retain(|&(attached, _)| attached != attachment);
+            attachments.push((attachment, size));
+        }
+        if let Attachment::Color(_) = attachment {
+            self.draw_to_all_colors();
+        }
+    }
+
+    // Indices of the color attachments, in order
+    fn color_indices(&self) -> Vec<u32> {
+        let mut indices: Vec<u32> = self
+            .attachments
+            .borrow()
+            .iter()
+            .filter_map(|&(attachment, _)| match attachment {
+                Attachment::Color(index) => Some(index),
+                _ => None,
+            })
+            .collect();
+        indices.sort_unstable();
+        indices
+    }
+
+    fn draw_to_all_colors(&self) {
+        let buffers: Vec<types::GLenum> = self.color_indices().iter().map(|&index| gl::COLOR_ATTACHMENT0 + index).collect();
+        if let Some(&first) = buffers.first() {
+            self.with_bound(|| unsafe {
+                gl::DrawBuffers(buffers.len() as types::GLsizei, buffers.as_ptr());
+                gl::ReadBuffer(first);
+            });
+        }
+    }
+
+    // Area every attachment covers, which is all that can be
+    // rendered to when their sizes differ; `None` until something
+    // is attached
+    pub fn size(&self) -> Option<(i32, i32)> {
+        self.attachments
+            .borrow()
+            .iter()
+            .map(|&(_, size)| size)
+            .reduce(|(width, height), (other_width, other_height)| (width.min(other_width), height.min(other_height)))
+    }
+
+    // Whether the attachments can be rendered to together; the
+    // error says what is missing or mismatched
+    pub fn check_status(&self) -> GfxResult<()> {
+        let status = self.with_bound(|| unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) });
+        if status == gl::FRAMEBUFFER_COMPLETE {
+            Ok(())
+        } else {
+            Err(GfxError::IncompleteFramebuffer(status))
+        }
+    }
+
+    // Copies `src` (x0, y0, x1, y1) of color attachment
+    // `read_attachment`, and depth or stencil if requested, into
+    // `dst` of `target`, or of the default framebuffer for `None`.
+    // Scaling with `Filter::Linear` is only allowed for color.
+    pub fn blit_to(
+        &self,
+        target: Option<&FrameBuffer>,
+        read_attachment: u32,
+        src: [i32; 4],
+        dst: [i32; 4],
+        buffers: BlitBuffers,
+        filter: Filter,
+    ) {
+        let target_id = target.map_or(0, |target| target.id);
+        let _binding = FrameBufferBinding::save();
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target_id);
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0 + read_attachment);
+            let filter = match filter {
+                Filter::Nearest => gl::NEAREST,
+                Filter::Linear => gl::LINEAR,
+            };
+            gl::BlitFramebuffer(src[0], src[1], src[2], src[3], dst[0], dst[1], dst[2], dst[3], buffers.0, filter);
+        }
+    }
+
+    // Resolves a multisampled framebuffer into `target`, which
+    // must have the same size: every color attachment goes to the
+    // attachment with the same index, depth and stencil are copied
+    // if `target` has them
+    pub fn resolve_to(&self, target: &FrameBuffer) -> GfxResult<()> {
+        let (width, height) = self.size().ok_or(GfxError::IncompleteFramebuffer(gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT))?;
+        let rect = [0, 0, width, height];
+        let target_colors = target.color_indices();
+        let mut first = true;
+        for index in self.color_indices().into_iter().filter(|index| target_colors.contains(index)) {
+            // Depth and stencil ride along with the first color copy
+            let buffers = if first { BlitBuffers::COLOR | BlitBuffers::DEPTH | BlitBuffers::STENCIL } else { BlitBuffers::COLOR };
+            target.with_bound(|| unsafe {
+                gl::DrawBuffer(gl::COLOR_ATTACHMENT0 + index);
+            });
+            self.blit_to(Some(target), index, rect, rect, buffers, Filter::Nearest);
+            first = false;
+        }
+        if first {
+            self.blit_to(Some(target), 0, rect, rect, BlitBuffers::DEPTH | BlitBuffers::STENCIL, Filter::Nearest);
+        }
+        // Put back drawing to every color attachment
+        target.draw_to_all_colors();
+        Ok(())
+    }
+
+    // Runs `f` with this framebuffer bound, then rebinds the
+    // previous draw and read framebuffers
+    fn with_bound<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _binding = FrameBufferBinding::save();
+        self.bind();
+        f()
+    }
+}
+
+impl Drop for FrameBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.id);
+        }
+    }
+}
+
+// The framebuffer bindings and viewport from before a
+// `bind_scoped()`, restored on drop
+#[derive(Debug)]
+pub struct FrameBufferBinding {
+    draw: types::GLuint,
+    read: types::GLuint,
+    viewport: [types::GLint; 4],
+}
+
+impl FrameBufferBinding {
+    fn save() -> FrameBufferBinding {
+        let (mut draw, mut read) = (0, 0);
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut draw);
+            gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut read);
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+        FrameBufferBinding { draw: draw as types::GLuint, read: read as types::GLuint, viewport }
+    }
+}
+
+impl Drop for FrameBufferBinding {
+    fn drop(&mut self) {
+        let [x, y, width, height] = self.viewport;
+        unsafe {
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.draw);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.read);
+            gl::Viewport(x, y, width, height);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RenderBuffer(types::GLuint);
+
+impl RenderBuffer {
+    pub fn new() -> GfxResult<RenderBuffer> {
+        let mut render_buffer = 0;
+        unsafe {
+            gl::GenRenderbuffers(1, &mut render_buffer);
+        }
+        if render_buffer != 0 {
+            Ok(RenderBuffer(render_buffer))
+        } else {
+            Err(GfxError::ObjectCreationError("Renderbuffer"))
+        }
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.0);
+        }
+    }
+
+    pub fn set_label(&self, label: &str) {
+        object_label(gl::RENDERBUFFER, self.0, label);
+    }
+
+    pub fn id(&self) -> u32 {
+        self.0
+    }
+
+    // (Re)allocates the renderbuffer, binding it
+    pub fn set_storage(&self, format: TextureFormat, width: i32, height: i32) {
+        self.bind();
+        unsafe {
+            gl::RenderbufferStorage(gl::RENDERBUFFER, format as types::GLenum, width, height);
+        }
+    }
+
+    // Like set_storage() with `samples` samples per pixel, at
+    // most `GlCapabilities::max_samples`; resolve the framebuffer
+    // it is attached to before sampling the result
+    pub fn set_storage_multisample(&self, format: TextureFormat, samples: i32, width: i32, height: i32) {
+        self.bind();
+        unsafe {
+            gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples, format as types::GLenum, width, height);
+        }
+    }
+
+    pub fn size(&self) -> (i32, i32) {
+        let (mut width, mut height) = (0, 0);
+        self.bind();
+        unsafe {
+            gl::GetRenderbufferParameteriv(gl::RENDERBUFFER, gl::RENDERBUFFER_WIDTH, &mut width);
+            gl::GetRenderbufferParameteriv(gl::RENDERBUFFER, gl::RENDERBUFFER_HEIGHT, &mut height);
+        }
+        (width, height)
+    }
+
+    // Samples per pixel, 0 for a single-sampled renderbuffer
+    pub fn samples(&self) -> i32 {
+        let mut samples = 0;
+        self.bind();
+        unsafe {
+            gl::GetRenderbufferParameteriv(gl::RENDERBUFFER, gl::RENDERBUFFER_SAMPLES, &mut samples);
+        }
+        samples
+    }
+}
+
+impl Drop for RenderBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteRenderbuffers(1, &self.0);
+        }
+    }
+}
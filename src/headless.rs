@@ -0,0 +1,254 @@
+//! Offscreen OpenGL rendering without a window or display server
+//!
+//! `HeadlessContext` creates an EGL context on Mesa's surfaceless
+//! platform and renders into an internal framebuffer, so it works
+//! on CI machines and render servers without X11 or a GPU (Mesa
+//! falls back to the llvmpipe software rasterizer when no GPU is
+//! available, set `LIBGL_ALWAYS_SOFTWARE=1` to force it).
+use crate::capabilities::reset_current;
+use crate::error::WindowError;
+use crate::{enable_debug_output, types, Attachment, FrameBuffer, FrameTiming, GfxError, GLSurface, HandlerResult, PixelArray, RenderBuffer, TextureFormat, WindowHandler};
+use elara_log::prelude::*;
+use khronos_egl as egl;
+use std::mem::ManuallyDrop;
+
+// From the EGL_MESA_platform_surfaceless extension
+const PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+// OpenGL versions to try in order of preference
+const GL_VERSIONS: [(egl::Int, egl::Int); 2] = [(4, 5), (3, 3)];
+
+pub struct HeadlessContext {
+    width: i32,
+    height: i32,
+    egl: egl::DynamicInstance<egl::EGL1_5>,
+    display: egl::Display,
+    context: egl::Context,
+    // Dropped by hand so that they are deleted
+    // before the context is destroyed
+    framebuffer: ManuallyDrop<FrameBuffer>,
+    color_buffer: ManuallyDrop<RenderBuffer>,
+    depth_buffer: ManuallyDrop<RenderBuffer>,
+    timing: FrameTiming,
+}
+
+impl HeadlessContext {
+    pub fn new(width: i32, height: i32) -> Result<HeadlessContext, WindowError> {
+        Self::create(width, height, false)
+    }
+
+    // Creates a debug context and routes KHR_debug
+    // messages from the driver into elara-log
+    pub fn new_debug(width: i32, height: i32) -> Result<HeadlessContext, WindowError> {
+        Self::create(width, height, true)
+    }
+
+    fn create(width: i32, height: i32, debug: bool) -> Result<HeadlessContext, WindowError> {
+        if rgba_len(width, height).is_none() {
+            return Err(WindowError::InvalidSize { width, height });
+        }
+        let egl = unsafe { egl::DynamicInstance::<egl::EGL1_5>::load_required() }
+            .map_err(|err| WindowError::EglLoadError(Box::new(err)))?;
+        let display = unsafe {
+            egl.get_platform_display(PLATFORM_SURFACELESS_MESA, egl::DEFAULT_DISPLAY, &[egl::ATTRIB_NONE])
+        }?;
+        egl.initialize(display)?;
+        let mut guard = SetupGuard { egl: &egl, display, context: None };
+        let context = Self::create_context(&egl, display, debug)?;
+        guard.context = Some(context);
+        // Surfaceless contexts have no default framebuffer,
+        // all rendering goes to our own framebuffer instead
+        egl.make_current(display, None, None, Some(context))?;
+        gl::load_with(|symbol| {
+            egl.get_proc_address(symbol)
+                .map_or(std::ptr::null(), |ptr| ptr as *const types::c_void)
+        });
+        reset_current();
+        if debug {
+            enable_debug_output();
+        }
+
+        let framebuffer = FrameBuffer::new().map_err(WindowError::FramebufferError)?;
+        let color_buffer = RenderBuffer::new().map_err(WindowError::FramebufferError)?;
+        let depth_buffer = RenderBuffer::new().map_err(WindowError::FramebufferError)?;
+        // From here on the Drop of `HeadlessContext` cleans up
+        std::mem::forget(guard);
+        let headless = HeadlessContext {
+            width,
+            height,
+            egl,
+            display,
+            context,
+            framebuffer: ManuallyDrop::new(framebuffer),
+            color_buffer: ManuallyDrop::new(color_buffer),
+            depth_buffer: ManuallyDrop::new(depth_buffer),
+            timing: FrameTiming::new(),
+        };
+        headless.allocate_framebuffer();
+        headless.framebuffer.check_status().map_err(WindowError::FramebufferError)?;
+        Ok(headless)
+    }
+
+    fn create_context(
+        egl: &egl::DynamicInstance<egl::EGL1_5>,
+        display: egl::Display,
+        debug: bool,
+    ) -> Result<egl::Context, WindowError> {
+        egl.bind_api(egl::OPENGL_API)?;
+        // A surface type of 0 matches every config since
+        // we never create an EGL surface
+        let config_attribs = [
+            egl::RENDERABLE_TYPE, egl::OPENGL_BIT,
+            egl::SURFACE_TYPE, 0,
+            egl::NONE,
+        ];
+        let config = match egl.choose_first_config(display, &config_attribs)? {
+            Some(config) => config,
+            None => {
+                error!("[elara-gfx] No EGL config supports OpenGL rendering");
+                return Err(WindowError::PlatformError);
+            }
+        };
+        let mut last_err = WindowError::PlatformError;
+        for (major, minor) in GL_VERSIONS {
+            let context_attribs = [
+                egl::CONTEXT_MAJOR_VERSION, major,
+                egl::CONTEXT_MINOR_VERSION, minor,
+                egl::CONTEXT_OPENGL_PROFILE_MASK, egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+                egl::CONTEXT_OPENGL_DEBUG, if debug { egl::TRUE as egl::Int } else { egl::FALSE as egl::Int },
+                egl::NONE,
+            ];
+            match egl.create_context(display, config, None, &context_attribs) {
+                Ok(context) => return Ok(context),
+                Err(err) => {
+                    warn!("[elara-gfx] OpenGL {}.{} context creation failed: {}", major, minor, err);
+                    last_err = err.into();
+                }
+            }
+        }
+        Err(last_err)
+    }
+
+    fn allocate_framebuffer(&self) {
+        self.framebuffer.bind();
+        self.color_buffer.set_storage(TextureFormat::Rgba8, self.width, self.height);
+        self.framebuffer.attach_renderbuffer(Attachment::Color(0), &self.color_buffer);
+        self.depth_buffer.set_storage(TextureFormat::Depth24Stencil8, self.width, self.height);
+        self.framebuffer.attach_renderbuffer(Attachment::DepthStencil, &self.depth_buffer);
+        unsafe {
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            gl::Viewport(0, 0, self.width, self.height);
+        }
+    }
+
+    pub fn make_current(&self) {
+        if let Err(err) = self.egl.make_current(self.display, None, None, Some(self.context)) {
+            error!("[elara-gfx] Failed to make EGL context current: {}", err);
+        }
+        self.framebuffer.bind();
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    // Reallocates the internal framebuffer and notifies
+    // the handler, the same way a window resize would
+    pub fn resize<H: WindowHandler>(&mut self, width: i32, height: i32, handler: &mut H) -> HandlerResult<()> {
+        if rgba_len(width, height).is_none() {
+            return Err(GfxError::InvalidSize { width, height });
+        }
+        self.width = width;
+        self.height = height;
+        self.make_current();
+        self.allocate_framebuffer();
+        handler.on_resize(width, height)
+    }
+
+    // Runs one iteration of the render loop for `handler`
+    // and returns the rendered frame
+    pub fn render_frame<H: WindowHandler>(&mut self, handler: &mut H) -> HandlerResult<PixelArray> {
+        self.make_current();
+        let dt = self.timing.tick();
+        handler.on_update(dt, &self.timing)?;
+        handler.on_draw()?;
+        unsafe {
+            gl::Finish();
+        }
+        handler.post_draw()?;
+        Ok(self.read_pixels())
+    }
+
+    // Reads back the contents of the internal framebuffer,
+    // with the first row of the image at the top
+    pub fn read_pixels(&self) -> PixelArray {
+        self.framebuffer.bind();
+        // Checked when the size was set
+        let len = rgba_len(self.width, self.height).unwrap_or(0);
+        let mut buffer = vec![0_u8; len];
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            gl::ReadPixels(0, 0, self.width, self.height, gl::RGBA, gl::UNSIGNED_BYTE, buffer.as_mut_ptr() as *mut types::c_void);
+        }
+        let mut img = PixelArray::from_bytearray(buffer, self.width as usize, self.height as usize).unwrap();
+        img.flipv();
+        img
+    }
+}
+
+// Releases the display and context while `create()` is
+// still setting up, so that returning early doesn't leak them
+struct SetupGuard<'a> {
+    egl: &'a egl::DynamicInstance<egl::EGL1_5>,
+    display: egl::Display,
+    context: Option<egl::Context>,
+}
+
+impl Drop for SetupGuard<'_> {
+    fn drop(&mut self) {
+        let _ = self.egl.make_current(self.display, None, None, None);
+        if let Some(context) = self.context {
+            let _ = self.egl.destroy_context(self.display, context);
+        }
+        let _ = self.egl.terminate(self.display);
+    }
+}
+
+// Bytes in an RGBA8 image of the given size, `None` if the
+// size isn't positive or the image wouldn't fit in memory
+fn rgba_len(width: i32, height: i32) -> Option<usize> {
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+    (width as usize).checked_mul(height as usize)?.checked_mul(4)
+}
+
+impl GLSurface for HeadlessContext {
+    fn width(&self) -> i32 {
+        self.width
+    }
+
+    fn height(&self) -> i32 {
+        self.height
+    }
+}
+
+impl Drop for HeadlessContext {
+    fn drop(&mut self) {
+        let _ = self.egl.make_current(self.display, None, None, Some(self.context));
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            ManuallyDrop::drop(&mut self.framebuffer);
+            ManuallyDrop::drop(&mut self.color_buffer);
+            ManuallyDrop::drop(&mut self.depth_buffer);
+        }
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self.egl.destroy_context(self.display, self.context);
+        let _ = self.egl.terminate(self.display);
+    }
+}
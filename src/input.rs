@@ -0,0 +1,316 @@
+//! Input event types passed to `WindowHandler` callbacks
+//!
+//! These mirror the subset of winit's events that elara-gfx
+//! forwards so that handlers never need to depend on winit
+//! directly.
+use winit::event::{
+    ElementState, ModifiersState, MouseButton as WinitMouseButton, MouseScrollDelta,
+    VirtualKeyCode,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyState {
+    Pressed,
+    Released,
+}
+
+impl KeyState {
+    pub fn is_pressed(&self) -> bool {
+        *self == KeyState::Pressed
+    }
+}
+
+impl From<ElementState> for KeyState {
+    fn from(state: ElementState) -> KeyState {
+        match state {
+            ElementState::Pressed => KeyState::Pressed,
+            ElementState::Released => KeyState::Released,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+impl From<ModifiersState> for Modifiers {
+    fn from(state: ModifiersState) -> Modifiers {
+        Modifiers {
+            shift: state.shift(),
+            ctrl: state.ctrl(),
+            alt: state.alt(),
+            logo: state.logo(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Other(u16),
+}
+
+impl From<WinitMouseButton> for MouseButton {
+    fn from(button: WinitMouseButton) -> MouseButton {
+        match button {
+            WinitMouseButton::Left => MouseButton::Left,
+            WinitMouseButton::Right => MouseButton::Right,
+            WinitMouseButton::Middle => MouseButton::Middle,
+            WinitMouseButton::Other(n) => MouseButton::Other(n),
+        }
+    }
+}
+
+// Scroll amounts are given in lines for most mice
+// and in pixels for touchpads and other high-precision
+// devices
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScrollDelta {
+    Lines(f32, f32),
+    Pixels(f64, f64),
+}
+
+impl From<MouseScrollDelta> for ScrollDelta {
+    fn from(delta: MouseScrollDelta) -> ScrollDelta {
+        match delta {
+            MouseScrollDelta::LineDelta(x, y) => ScrollDelta::Lines(x, y),
+            MouseScrollDelta::PixelDelta(pos) => ScrollDelta::Pixels(pos.x, pos.y),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    // `None` for keys without a symbolic name, in that
+    // case use the platform-specific `scancode`
+    pub key: Option<Key>,
+    pub scancode: u32,
+    pub state: KeyState,
+    pub modifiers: Modifiers,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    Key0,
+    Key1,
+    Key2,
+    Key3,
+    Key4,
+    Key5,
+    Key6,
+    Key7,
+    Key8,
+    Key9,
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    Escape,
+    Tab,
+    Space,
+    Enter,
+    Backspace,
+    Insert,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Left,
+    Right,
+    Up,
+    Down,
+    Minus,
+    Equals,
+    LBracket,
+    RBracket,
+    Backslash,
+    Semicolon,
+    Apostrophe,
+    Grave,
+    Comma,
+    Period,
+    Slash,
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    NumpadAdd,
+    NumpadSubtract,
+    NumpadMultiply,
+    NumpadDivide,
+    NumpadDecimal,
+    NumpadEnter,
+    LShift,
+    RShift,
+    LControl,
+    RControl,
+    LAlt,
+    RAlt,
+    LLogo,
+    RLogo,
+    CapsLock,
+    NumLock,
+    ScrollLock,
+    PrintScreen,
+    Pause,
+}
+
+impl Key {
+    pub(crate) fn from_winit(keycode: VirtualKeyCode) -> Option<Key> {
+        use VirtualKeyCode as V;
+        let key = match keycode {
+            V::Key0 => Key::Key0,
+            V::Key1 => Key::Key1,
+            V::Key2 => Key::Key2,
+            V::Key3 => Key::Key3,
+            V::Key4 => Key::Key4,
+            V::Key5 => Key::Key5,
+            V::Key6 => Key::Key6,
+            V::Key7 => Key::Key7,
+            V::Key8 => Key::Key8,
+            V::Key9 => Key::Key9,
+            V::A => Key::A,
+            V::B => Key::B,
+            V::C => Key::C,
+            V::D => Key::D,
+            V::E => Key::E,
+            V::F => Key::F,
+            V::G => Key::G,
+            V::H => Key::H,
+            V::I => Key::I,
+            V::J => Key::J,
+            V::K => Key::K,
+            V::L => Key::L,
+            V::M => Key::M,
+            V::N => Key::N,
+            V::O => Key::O,
+            V::P => Key::P,
+            V::Q => Key::Q,
+            V::R => Key::R,
+            V::S => Key::S,
+            V::T => Key::T,
+            V::U => Key::U,
+            V::V => Key::V,
+            V::W => Key::W,
+            V::X => Key::X,
+            V::Y => Key::Y,
+            V::Z => Key::Z,
+            V::F1 => Key::F1,
+            V::F2 => Key::F2,
+            V::F3 => Key::F3,
+            V::F4 => Key::F4,
+            V::F5 => Key::F5,
+            V::F6 => Key::F6,
+            V::F7 => Key::F7,
+            V::F8 => Key::F8,
+            V::F9 => Key::F9,
+            V::F10 => Key::F10,
+            V::F11 => Key::F11,
+            V::F12 => Key::F12,
+            V::Escape => Key::Escape,
+            V::Tab => Key::Tab,
+            V::Space => Key::Space,
+            V::Return => Key::Enter,
+            V::Back => Key::Backspace,
+            V::Insert => Key::Insert,
+            V::Delete => Key::Delete,
+            V::Home => Key::Home,
+            V::End => Key::End,
+            V::PageUp => Key::PageUp,
+            V::PageDown => Key::PageDown,
+            V::Left => Key::Left,
+            V::Right => Key::Right,
+            V::Up => Key::Up,
+            V::Down => Key::Down,
+            V::Minus => Key::Minus,
+            V::Equals => Key::Equals,
+            V::LBracket => Key::LBracket,
+            V::RBracket => Key::RBracket,
+            V::Backslash => Key::Backslash,
+            V::Semicolon => Key::Semicolon,
+            V::Apostrophe => Key::Apostrophe,
+            V::Grave => Key::Grave,
+            V::Comma => Key::Comma,
+            V::Period => Key::Period,
+            V::Slash => Key::Slash,
+            V::Numpad0 => Key::Numpad0,
+            V::Numpad1 => Key::Numpad1,
+            V::Numpad2 => Key::Numpad2,
+            V::Numpad3 => Key::Numpad3,
+            V::Numpad4 => Key::Numpad4,
+            V::Numpad5 => Key::Numpad5,
+            V::Numpad6 => Key::Numpad6,
+            V::Numpad7 => Key::Numpad7,
+            V::Numpad8 => Key::Numpad8,
+            V::Numpad9 => Key::Numpad9,
+            V::NumpadAdd => Key::NumpadAdd,
+            V::NumpadSubtract => Key::NumpadSubtract,
+            V::NumpadMultiply => Key::NumpadMultiply,
+            V::NumpadDivide => Key::NumpadDivide,
+            V::NumpadDecimal => Key::NumpadDecimal,
+            V::NumpadEnter => Key::NumpadEnter,
+            V::LShift => Key::LShift,
+            V::RShift => Key::RShift,
+            V::LControl => Key::LControl,
+            V::RControl => Key::RControl,
+            V::LAlt => Key::LAlt,
+            V::RAlt => Key::RAlt,
+            V::LWin => Key::LLogo,
+            V::RWin => Key::RLogo,
+            V::Capital => Key::CapsLock,
+            V::Numlock => Key::NumLock,
+            V::Scroll => Key::ScrollLock,
+            V::Snapshot => Key::PrintScreen,
+            V::Pause => Key::Pause,
+            _ => return None,
+        };
+        Some(key)
+    }
+}
@@ -2,19 +2,61 @@
 
 use canvas::Canvas;
 pub use gl;
-use raw_gl_context::{GlConfig, GlContext};
+use raw_gl_context::{GlConfig, GlContext, Profile};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ffi::{CStr, CString};
+use std::time::{Duration, Instant};
 use winit::dpi::PhysicalSize;
 pub use winit::event::Event;
+use winit::event::WindowEvent;
 pub use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::{Window, WindowBuilder};
+use winit::platform::run_return::EventLoopExtRunReturn;
+use winit::window::{Fullscreen, Window, WindowBuilder};
 // pub mod gfx;
 mod error;
 pub mod types;
 use elara_log::prelude::*;
-use error::*;
+pub use error::*;
 mod image;
-pub use image::{PixelArray, RGBA};
+pub use image::{ImageError, PixelArray, RGBA};
+mod input;
+pub use input::{Key, KeyEvent, KeyState, Modifiers, MouseButton, ScrollDelta};
+mod timing;
+pub use timing::{FrameTiming, RedrawMode};
+mod headless;
+pub use headless::HeadlessContext;
+mod debug;
+pub use debug::{enable_debug_output, is_debug_enabled, DebugGroup};
+use debug::object_label;
+mod capabilities;
+pub use capabilities::GlCapabilities;
+mod buffer;
+mod draw;
+pub use draw::{enable_alpha_blend, set_patch_vertices, DrawRange, IndexType, PrimitiveMode};
+pub use buffer::{Buffer, BufferReadGuard, BufferWriteGuard, Pod, RingBuffer};
+mod uniform;
+pub use uniform::{glsl_type_name, AttributeInfo, Sampler, UniformBlockInfo, UniformInfo, UniformValue};
+mod diagnostics;
+pub use diagnostics::{parse_info_log, Severity, ShaderDiagnostic};
+mod preprocessor;
+pub use preprocessor::{EmbeddedIncludeResolver, FileIncludeResolver, IncludeResolver, PreprocessedSource, ShaderSource};
+mod program_cache;
+pub use program_cache::ProgramCache;
+mod reload;
+pub use reload::{ReloadableProgram, StageSource};
+mod compute;
+pub use compute::{memory_barrier, DispatchIndirectCommand, ImageAccess, MemoryBarrier};
+mod texture;
+pub use texture::{mip_level_count, set_unpack_alignment, CubeFace, PixelType, Texture2DArray, Texture3D, TextureCube, TextureFormat};
+mod sampler;
+pub use sampler::{CompareFunc, Filter, MipmapMode, SamplerDesc, SamplerObject, Wrap};
+mod framebuffer;
+pub use framebuffer::{Attachment, BlitBuffers, FrameBuffer, FrameBufferBinding, RenderBuffer};
+mod std140;
+pub use std140::{to_std140, Std140, Std140Array, Std140Writer, UniformBuffer};
+pub mod vertex;
+pub use vertex::{AttribKind, Normalized, VertexAttribFormat, VertexAttribute, VertexLayout};
 pub mod canvas;
 
 // NOTE: elara-gfx uses elara-log internally to log
@@ -54,28 +96,47 @@ pub fn gl_info() {
     }
 }
 
-pub fn check_gl_error() {
-    let error_code = unsafe { gl::GetError() };
-    if error_code != gl::NO_ERROR {
-        let error_str = match error_code {
-            gl::INVALID_ENUM => "Invalid enum",
-            gl::INVALID_VALUE => "Invalid value",
-            gl::INVALID_OPERATION => "Invalid operation",
-            gl::STACK_OVERFLOW => "Stack overflow",
-            gl::STACK_UNDERFLOW => "Stack underflow",
-            gl::OUT_OF_MEMORY => "Out of memory",
-            gl::INVALID_FRAMEBUFFER_OPERATION => "Invalid framebuffer operation",
-            _ => "Unknown GL error"
-        };
-        error!("{:?} | {}:{}", error_str, file!(), line!());
-    } else {
-        info!("No errors detected");
+// GL keeps a queue of error flags, so drain all of them and
+// report the first; #[track_caller] makes the logged location
+// point at the caller rather than at this function
+#[track_caller]
+pub fn check_gl_error() -> GfxResult<()> {
+    // Some drivers keep returning errors when no context is
+    // current, so don't loop forever
+    const MAX_ERRORS: usize = 32;
+    let location = std::panic::Location::caller();
+    let mut first_error = None;
+    for _ in 0..MAX_ERRORS {
+        let error_code = unsafe { gl::GetError() };
+        if error_code == gl::NO_ERROR {
+            break;
+        }
+        error!("{:?} | {}:{}", gl_error_name(error_code), location.file(), location.line());
+        first_error.get_or_insert(error_code);
+    }
+    match first_error {
+        Some(error_code) => Err(GfxError::GlErrorCode(error_code)),
+        None => {
+            info!("No errors detected");
+            Ok(())
+        }
     }
 }
 
 
-// Temporary: all WindowHandler errors use strings
-pub type HandlerResult<T> = Result<T, String>;
+pub type HandlerResult<T> = GfxResult<T>;
+
+// What the render loop should do when a handler returns an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    // Stop the loop and return the error from `run_loop()`
+    #[default]
+    Exit,
+    // Log the error and keep running
+    LogAndContinue,
+    // Stop the loop without treating the error as a failure
+    Stop,
+}
 
 // Temporary: type for defining pointer-obtainable types
 pub trait PointerType: Sized {
@@ -83,7 +144,22 @@ pub trait PointerType: Sized {
     fn as_ptr(&self) -> *mut Self::CType;
 }
 
+// Usually owns the application's GL objects, which delete
+// themselves on drop and so must be dropped while their context
+// is current; `run_loop()` takes care of that for the handler
 pub trait WindowHandler {
+    // Called whenever another handler method returns an error,
+    // the returned policy decides whether the loop keeps running
+    fn on_error(&mut self, _error: &GfxError) -> ErrorPolicy {
+        ErrorPolicy::default()
+    }
+
+    // Called once before every frame is drawn with the time
+    // since the last frame `dt` in seconds
+    fn on_update(&mut self, _dt: f32, _timing: &FrameTiming) -> HandlerResult<()> {
+        Ok(())
+    }
+
     fn on_draw(&mut self) -> HandlerResult<()> {
         Ok(())
     }
@@ -91,7 +167,41 @@ pub trait WindowHandler {
     fn post_draw(&mut self) -> HandlerResult<()> {
     	Ok(())
     }
-    fn on_resize(&mut self) {}
+    // Called after the window has been resized with the new
+    // size in physical pixels, the viewport is already updated
+    // by the time this is called
+    fn on_resize(&mut self, _width: i32, _height: i32) -> HandlerResult<()> {
+        Ok(())
+    }
+
+    fn on_key(&mut self, _event: KeyEvent) -> HandlerResult<()> {
+        Ok(())
+    }
+
+    // Cursor position is given in physical pixels
+    // relative to the top-left corner of the window
+    fn on_mouse_move(&mut self, _x: f64, _y: f64) -> HandlerResult<()> {
+        Ok(())
+    }
+
+    fn on_mouse_button(&mut self, _button: MouseButton, _state: KeyState, _modifiers: Modifiers) -> HandlerResult<()> {
+        Ok(())
+    }
+
+    fn on_scroll(&mut self, _delta: ScrollDelta) -> HandlerResult<()> {
+        Ok(())
+    }
+
+    fn on_focus(&mut self, _focused: bool) -> HandlerResult<()> {
+        Ok(())
+    }
+
+    // Receives text input after keyboard layout and
+    // dead keys have been applied, use this instead
+    // of on_key() for text entry
+    fn on_char(&mut self, _c: char) -> HandlerResult<()> {
+        Ok(())
+    }
 
     fn save_rendering(&self, width: i32, height: i32) -> Option<PixelArray> {
     	// source: https://lencerf.github.io/post/2019-09-21-save-the-opengl-rendering-to-image-file/
@@ -100,19 +210,32 @@ pub trait WindowHandler {
         stride += if stride % 4 != 0 { 4 - stride % 4 } else { 0 };
         let buffer_size = stride * height;
         let buffer = vec![0; buffer_size as usize];
+        let mut read_framebuffer = 0;
         unsafe {
+            gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut read_framebuffer);
 	        gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
-	        gl::ReadBuffer(gl::FRONT);
+	        // Offscreen framebuffers (e.g. in a HeadlessContext)
+	        // have no front buffer to read from
+	        if read_framebuffer == 0 {
+	            gl::ReadBuffer(gl::FRONT);
+	        } else {
+	            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+	        }
 	        gl::ReadPixels(0, 0, width, height, gl::RGBA, gl::UNSIGNED_BYTE, buffer.as_ptr() as *mut u8 as *mut types::c_void);
         }
         PixelArray::from_bytearray(buffer, width as usize, height as usize)
     }
-    // TODO: add other methods such as on_mouse_move(), on_keydown(),
-    // on_click(), on_cursor_move() for handling on non-draw events
+}
+
+// Anything that OpenGL can render to, either a window
+// or an offscreen HeadlessContext
+pub trait GLSurface {
+    fn width(&self) -> i32;
+    fn height(&self) -> i32;
 }
 
 pub trait Draw {
-    fn draw(&mut self, win: &GLWindow) -> Result<Canvas, String> {
+    fn draw(&mut self, win: &dyn GLSurface) -> GfxResult<Canvas> {
         Ok(Canvas::new(win))
     }
 }
@@ -120,41 +243,176 @@ pub trait Draw {
 #[derive(Debug)]
 pub struct GLWindowHandler {
     event_loop: EventLoop<()>,
+    redraw_mode: RedrawMode,
 }
 
 impl GLWindowHandler {
     pub fn new() -> GLWindowHandler {
         GLWindowHandler {
             event_loop: EventLoop::new(),
+            redraw_mode: RedrawMode::default(),
         }
     }
 
-    pub fn run_loop<H>(self, window: GLWindow, mut handler: H)
+    // Fails for a `TargetFps` that isn't a positive, finite
+    // frame rate, leaving the previous mode in place
+    pub fn set_redraw_mode(&mut self, mode: RedrawMode) -> GfxResult<()> {
+        if let RedrawMode::TargetFps(fps) = mode {
+            let interval = Duration::try_from_secs_f64(1.0 / fps);
+            if !fps.is_finite() || !interval.is_ok_and(|interval| !interval.is_zero()) {
+                return Err(GfxError::InvalidFrameRate(fps));
+            }
+        }
+        self.redraw_mode = mode;
+        Ok(())
+    }
+
+    pub fn redraw_mode(&self) -> RedrawMode {
+        self.redraw_mode
+    }
+
+    // Runs the event loop until the window is closed or a handler
+    // error stops it, returning the error that ended the loop.
+    // GL objects must be dropped while their context is current,
+    // so the handler is dropped here with the window's context
+    // made current, before the window and its context go away.
+    pub fn run_loop<H>(self, mut window: GLWindow, mut handler: H) -> HandlerResult<()>
     where
         H: WindowHandler + 'static,
     {
-        self.event_loop.run(move |event, _, control_flow| {
-            match event {
+        let mut event_loop = self.event_loop;
+        let redraw_mode = self.redraw_mode;
+        let mut modifiers = Modifiers::default();
+        let mut timing = FrameTiming::new();
+        let mut next_frame = Instant::now();
+        let mut final_error = None;
+        event_loop.run_return(|event, _, control_flow| {
+            let result = match event {
                 Event::WindowEvent {
-                    event: winit::event::WindowEvent::CloseRequested,
+                    event: WindowEvent::CloseRequested,
                     ..
                 } => {
                     info!("[elara-gfx] Close request received, exiting...");
                     control_flow.set_exit();
+                    Ok(())
                 }
+                Event::WindowEvent { event, .. } => match event {
+                    WindowEvent::Resized(size) => {
+                        Self::handle_resize(&mut window, &mut handler, size)
+                    }
+                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                        Self::handle_resize(&mut window, &mut handler, *new_inner_size)
+                    }
+                    WindowEvent::ModifiersChanged(state) => {
+                        modifiers = state.into();
+                        Ok(())
+                    }
+                    WindowEvent::KeyboardInput { input, .. } => {
+                        let key_event = KeyEvent {
+                            key: input.virtual_keycode.and_then(Key::from_winit),
+                            scancode: input.scancode,
+                            state: input.state.into(),
+                            modifiers,
+                        };
+                        handler.on_key(key_event)
+                    }
+                    WindowEvent::ReceivedCharacter(c) => handler.on_char(c),
+                    WindowEvent::CursorMoved { position, .. } => {
+                        handler.on_mouse_move(position.x, position.y)
+                    }
+                    WindowEvent::MouseInput { state, button, .. } => {
+                        handler.on_mouse_button(button.into(), state.into(), modifiers)
+                    }
+                    WindowEvent::MouseWheel { delta, .. } => handler.on_scroll(delta.into()),
+                    WindowEvent::Focused(focused) => handler.on_focus(focused),
+                    _ => Ok(()),
+                },
                 Event::MainEventsCleared => {
+                    match redraw_mode {
+                        RedrawMode::OnDemand => control_flow.set_wait(),
+                        RedrawMode::Continuous => {
+                            window.request_redraw();
+                            control_flow.set_poll();
+                        }
+                        RedrawMode::TargetFps(fps) => {
+                            let now = Instant::now();
+                            if now >= next_frame {
+                                window.request_redraw();
+                                let frame_interval = Duration::from_secs_f64(1.0 / fps);
+                                next_frame += frame_interval;
+                                // Skip missed frames rather than trying
+                                // to catch up after a stall
+                                if next_frame < now {
+                                    next_frame = now + frame_interval;
+                                }
+                            }
+                            control_flow.set_wait_until(next_frame);
+                        }
+                    }
+                    Ok(())
                 }
                 Event::RedrawRequested(_) => {
-                    // Render function
                     window.make_current();
-                    handler.on_draw().unwrap();
-                    window.swap_buffers();
-                    handler.post_draw().unwrap();
+                    let result = Self::draw_frame(&window, &mut handler, &mut timing);
                     window.make_not_current();
+                    result
+                }
+                _ => Ok(()),
+            };
+            if let Err(err) = result {
+                match handler.on_error(&err) {
+                    ErrorPolicy::Exit => {
+                        error!("[elara-gfx] {}", err);
+                        final_error = Some(err);
+                        control_flow.set_exit();
+                    }
+                    ErrorPolicy::LogAndContinue => {
+                        warn!("[elara-gfx] {}", err);
+                    }
+                    ErrorPolicy::Stop => {
+                        info!("[elara-gfx] Handler stopped the event loop: {}", err);
+                        control_flow.set_exit();
+                    }
                 }
-                _ => {}
             }
         });
+        window.make_current();
+        drop(handler);
+        window.make_not_current();
+        match final_error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+impl GLWindowHandler {
+    fn draw_frame<H: WindowHandler>(window: &GLWindow, handler: &mut H, timing: &mut FrameTiming) -> HandlerResult<()> {
+        let dt = timing.tick();
+        handler.on_update(dt, timing)?;
+        // Render function
+        handler.on_draw()?;
+        window.swap_buffers();
+        handler.post_draw()
+    }
+
+    fn handle_resize<H: WindowHandler>(window: &mut GLWindow, handler: &mut H, size: PhysicalSize<u32>) -> HandlerResult<()> {
+        let (width, height) = (size.width as i32, size.height as i32);
+        // Minimized windows report a size of zero, which would
+        // only produce divide-by-zero aspect ratios downstream
+        if width == 0 || height == 0 || (width, height) == (window.width, window.height) {
+            return Ok(());
+        }
+        window.width = width;
+        window.height = height;
+        window.make_current();
+        unsafe {
+            gl::Viewport(0, 0, width, height);
+        }
+        let result = handler.on_resize(width, height);
+        window.make_not_current();
+        window.request_redraw();
+        result
     }
 }
 
@@ -169,34 +427,168 @@ pub struct GLWindow {
     height: i32,
     base_window: Window,
     is_visible: bool,
+    samples: Option<u8>,
+    srgb: bool,
+    debug: bool,
     context: GlContext,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlProfile {
+    Core,
+    Compatibility,
+}
+
+impl From<GlProfile> for Profile {
+    fn from(profile: GlProfile) -> Profile {
+        match profile {
+            GlProfile::Core => Profile::Core,
+            GlProfile::Compatibility => Profile::Compatibility,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct WindowOptions {
-    pub title: &'static str,
+    pub title: String,
     pub width: i32,
     pub height: i32,
     pub is_visible: bool,
+    pub gl_version: (u8, u8),
+    pub gl_profile: GlProfile,
+    // Number of MSAA samples, `None` disables multisampling
+    pub samples: Option<u8>,
+    pub vsync: bool,
+    pub depth_bits: u8,
+    pub stencil_bits: u8,
+    pub srgb: bool,
+    pub resizable: bool,
+    pub fullscreen: bool,
+    pub decorations: bool,
+    pub min_size: Option<(i32, i32)>,
+    pub max_size: Option<(i32, i32)>,
+    // Route KHR_debug messages from the driver into elara-log;
+    // windows can't request a debug context, so this relies on
+    // the driver exposing KHR_debug on a regular one
+    pub debug: bool,
 }
 
 impl WindowOptions {
-    pub fn new(title: &'static str, width: i32, height: i32, is_visible: bool) -> WindowOptions {
+    pub fn new(title: impl Into<String>, width: i32, height: i32, is_visible: bool) -> WindowOptions {
         WindowOptions {
-            title,
+            title: title.into(),
             width,
             height,
             is_visible,
+            ..WindowOptions::default()
         }
     }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn size(mut self, width: i32, height: i32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn visible(mut self, is_visible: bool) -> Self {
+        self.is_visible = is_visible;
+        self
+    }
+
+    pub fn gl_version(mut self, major: u8, minor: u8) -> Self {
+        self.gl_version = (major, minor);
+        self
+    }
+
+    pub fn gl_profile(mut self, profile: GlProfile) -> Self {
+        self.gl_profile = profile;
+        self
+    }
+
+    pub fn samples(mut self, samples: Option<u8>) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn vsync(mut self, vsync: bool) -> Self {
+        self.vsync = vsync;
+        self
+    }
+
+    pub fn depth_bits(mut self, bits: u8) -> Self {
+        self.depth_bits = bits;
+        self
+    }
+
+    pub fn stencil_bits(mut self, bits: u8) -> Self {
+        self.stencil_bits = bits;
+        self
+    }
+
+    // Requests an sRGB-capable framebuffer and enables
+    // GL_FRAMEBUFFER_SRGB so output is gamma-corrected
+    pub fn srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    // Borderless fullscreen on the current monitor
+    pub fn fullscreen(mut self, fullscreen: bool) -> Self {
+        self.fullscreen = fullscreen;
+        self
+    }
+
+    pub fn decorations(mut self, decorations: bool) -> Self {
+        self.decorations = decorations;
+        self
+    }
+
+    pub fn min_size(mut self, width: i32, height: i32) -> Self {
+        self.min_size = Some((width, height));
+        self
+    }
+
+    pub fn max_size(mut self, width: i32, height: i32) -> Self {
+        self.max_size = Some((width, height));
+        self
+    }
+
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
 }
 
 impl Default for WindowOptions {
     fn default() -> WindowOptions {
         WindowOptions {
-            title: "OpenGL window",
+            title: String::from("OpenGL window"),
             width: 1200,
             height: 900,
             is_visible: true,
+            gl_version: (3, 2),
+            gl_profile: GlProfile::Core,
+            samples: Some(4),
+            vsync: false,
+            depth_bits: 24,
+            stencil_bits: 8,
+            srgb: false,
+            resizable: true,
+            fullscreen: false,
+            decorations: true,
+            min_size: None,
+            max_size: None,
+            debug: false,
         }
     }
 }
@@ -204,40 +596,61 @@ impl Default for WindowOptions {
 impl GLWindow {
     pub fn new(opts: WindowOptions) -> Result<(GLWindowHandler, GLWindow), WindowError> {
         let window_handler = GLWindowHandler::new();
-        let window = WindowBuilder::new()
-            .with_title(opts.title)
+        let mut builder = WindowBuilder::new()
+            .with_title(&opts.title)
             .with_inner_size(PhysicalSize::new(opts.width, opts.height))
             .with_visible(opts.is_visible)
-            .build(&window_handler.event_loop)
-            .unwrap();
-        // Enable MSAA
-        let config = GlConfig { 
-            samples: Some(4), 
+            .with_resizable(opts.resizable)
+            .with_decorations(opts.decorations);
+        if opts.fullscreen {
+            builder = builder.with_fullscreen(Some(Fullscreen::Borderless(None)));
+        }
+        if let Some((width, height)) = opts.min_size {
+            builder = builder.with_min_inner_size(PhysicalSize::new(width, height));
+        }
+        if let Some((width, height)) = opts.max_size {
+            builder = builder.with_max_inner_size(PhysicalSize::new(width, height));
+        }
+        let window = builder.build(&window_handler.event_loop)?;
+        let config = GlConfig {
+            version: opts.gl_version,
+            profile: opts.gl_profile.into(),
+            depth_bits: opts.depth_bits,
+            stencil_bits: opts.stencil_bits,
+            samples: opts.samples,
+            srgb: opts.srgb,
+            vsync: opts.vsync,
             ..GlConfig::default()
         };
-        let context = GlContext::create(&window, config).unwrap();
-        
+        let context = GlContext::create(&window, config)?;
+
+        // The actual size may differ from the requested
+        // size, e.g. for fullscreen windows
+        let size = window.inner_size();
         let gl_window = GLWindow {
-            width: opts.width,
-            height: opts.height,
+            width: size.width as i32,
+            height: size.height as i32,
             base_window: window,
             is_visible: opts.is_visible,
+            samples: opts.samples,
+            srgb: opts.srgb,
+            debug: opts.debug,
             context,
         };
         Ok((window_handler, gl_window))
     }
 
-    pub fn new_with_title(title: &'static str) -> Result<(GLWindowHandler, GLWindow), WindowError> {
+    pub fn new_with_title(title: impl Into<String>) -> Result<(GLWindowHandler, GLWindow), WindowError> {
         let opts = WindowOptions {
-            title,
+            title: title.into(),
             ..WindowOptions::default()
         };
         Self::new(opts)
     }
 
-    pub fn new_sized(title: &'static str, width: i32, height: i32) -> Result<(GLWindowHandler, GLWindow), WindowError> {
+    pub fn new_sized(title: impl Into<String>, width: i32, height: i32) -> Result<(GLWindowHandler, GLWindow), WindowError> {
         let opts = WindowOptions {
-            title,
+            title: title.into(),
             width, 
             height,
             ..WindowOptions::default()
@@ -245,12 +658,21 @@ impl GLWindow {
         Self::new(opts)
     }
 
-    pub fn get_context(&self) -> GlResult<()> {
+    pub fn get_context(&self) -> GfxResult<()> {
         self.context.make_current();
         gl::load_with(|symbol| self.context.get_proc_address(symbol) as *const types::c_void);
-        // Enable MSAA
+        capabilities::reset_current();
         unsafe { 
-            gl::Enable(gl::MULTISAMPLE);
+            // Enable MSAA
+            if self.samples.is_some() {
+                gl::Enable(gl::MULTISAMPLE);
+            }
+            if self.srgb {
+                gl::Enable(gl::FRAMEBUFFER_SRGB);
+            }
+        }
+        if self.debug && !enable_debug_output() {
+            return Err(GfxError::Unsupported("debug output"));
         }
         Ok(())
     }
@@ -273,7 +695,17 @@ impl GLWindow {
     }
 
     pub fn inner_height(&self) -> i32 {
-        self.base_window.inner_size().width as i32
+        self.base_window.inner_size().height as i32
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.base_window.scale_factor()
+    }
+
+    // Asks the event loop to draw a new frame, mainly
+    // useful with `RedrawMode::OnDemand`
+    pub fn request_redraw(&self) {
+        self.base_window.request_redraw();
     }
 
     pub fn set_title(&mut self, title: &str) {
@@ -314,19 +746,36 @@ impl GLWindow {
     }
 }
 
-pub struct Texture2D(pub types::GLuint);
+impl GLSurface for GLWindow {
+    fn width(&self) -> i32 {
+        self.width
+    }
+
+    fn height(&self) -> i32 {
+        self.height
+    }
+}
+
+// GL object wrappers own their object name and delete it
+// on drop; they are deliberately not Clone, wrap them in an
+// `Rc` when several owners need to share one object
+#[derive(Debug)]
+pub struct Texture2D(types::GLuint);
 
 impl Texture2D {
-    pub fn new() -> Result<Texture2D, String> {
+    pub fn new() -> GfxResult<Texture2D> {
         let mut texture = 0;
         unsafe { gl::GenTextures(1, &mut texture) };
         if texture != 0 {
             Ok(Texture2D(texture))
         } else {
-            let err = String::from("Texture2D creation failed");
-            Err(format!("[elara-gfx] {}", err))
+            Err(GfxError::ObjectCreationError("Texture2D"))
         }
     }
+
+    pub fn id(&self) -> types::GLuint {
+        self.0
+    }
     
     pub fn bind(&self) {
         unsafe { gl::BindTexture(gl::TEXTURE_2D, self.0) }
@@ -335,6 +784,12 @@ impl Texture2D {
     pub fn unbind(&self) {
         unsafe { gl::BindTexture(gl::TEXTURE_2D, 0) }
     }
+
+    // Names the object in debug output and graphics debuggers,
+    // the object must have been bound at least once
+    pub fn set_label(&self, label: &str) {
+        object_label(gl::TEXTURE, self.0, label);
+    }
     
     pub fn parameter_2d(&self, pname: types::GLenum, param: types::GLint) {
         unsafe {
@@ -342,13 +797,6 @@ impl Texture2D {
         }
     }
     
-    pub fn enable_alpha_blend(&self) {
-        unsafe {
-            gl::Enable(gl::BLEND);
-            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
-        }
-    }
-    
     pub fn generate_mipmap(&self) {
         unsafe {
             gl::GenerateMipmap(gl::TEXTURE_2D);
@@ -356,10 +804,11 @@ impl Texture2D {
     }
     
     pub fn set_image_2d(&self, img: PixelArray) {
+        // A sized internal format, image units reject plain RGBA
         unsafe {
             gl::TexImage2D(gl::TEXTURE_2D,
                            0,
-                           gl::RGBA as i32,
+                           gl::RGBA8 as i32,
                            img.width as i32,
                            img.height as i32,
                            0,
@@ -370,18 +819,81 @@ impl Texture2D {
     }
 }
 
-pub struct VertexArray(pub types::GLuint);
+impl Drop for Texture2D {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.0);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct VertexArray(types::GLuint);
 
 impl VertexArray {
-    pub fn new() -> Result<VertexArray, String> {
+    pub fn new() -> GfxResult<VertexArray> {
         let mut vao = 0;
         unsafe { gl::GenVertexArrays(1, &mut vao) };
         if vao != 0 {
             Ok(VertexArray(vao))
         } else {
-            let err = String::from("Vertex array creation failed");
-            Err(format!("[elara-gfx] {}", err))
+            Err(GfxError::ObjectCreationError("Vertex array"))
+        }
+    }
+
+    // Creates a vertex array reading `V` vertices from `buffer`,
+    // with every attribute of `V` looked up by name in `program`;
+    // the buffer may also hold the vertices as flat `f32`s
+    pub fn with_layout<V: VertexLayout>(program: &Program, buffer: &Buffer<impl Copy>) -> GfxResult<VertexArray> {
+        let vao = VertexArray::new()?;
+        vao.bind();
+        buffer.bind(BufferType::Array);
+        vao.set_layout::<V>(program);
+        vao.unbind();
+        buffer.unbind(BufferType::Array);
+        Ok(vao)
+    }
+
+    pub fn id(&self) -> types::GLuint {
+        self.0
+    }
+
+    // Configures the attributes of `V` for the buffer currently
+    // bound to GL_ARRAY_BUFFER, the vertex array must be bound;
+    // attributes the shader does not use are skipped. Returns
+    // the attribute indices that were enabled
+    pub fn set_layout<V: VertexLayout>(&self, program: &Program) -> Vec<types::GLuint> {
+        let stride = V::stride() as types::GLsizei;
+        let mut enabled = Vec::new();
+        for attrib in V::attributes() {
+            let location = self.get_attrib_location(program, attrib.name);
+            if location < 0 {
+                // Unused inputs are removed by the GLSL compiler
+                warn!("[elara-gfx] Vertex attribute `{}` is not active in the program, skipping", attrib.name);
+                continue;
+            }
+            for column in 0..attrib.columns {
+                let index = attrib.column_location(location as types::GLuint, column);
+                let offset = (attrib.offset + column as usize * attrib.column_size()) as *const types::c_void;
+                unsafe {
+                    match attrib.kind {
+                        AttribKind::Float | AttribKind::Normalized => gl::VertexAttribPointer(
+                            index,
+                            attrib.components,
+                            attrib.gl_type,
+                            (attrib.kind == AttribKind::Normalized) as types::GLboolean,
+                            stride,
+                            offset,
+                        ),
+                        AttribKind::Integer => gl::VertexAttribIPointer(index, attrib.components, attrib.gl_type, stride, offset),
+                        AttribKind::Double => gl::VertexAttribLPointer(index, attrib.components, attrib.gl_type, stride, offset),
+                    }
+                }
+                self.enable_vertex_attrib(index);
+                enabled.push(index);
+            }
         }
+        enabled
     }
 
     pub fn bind(&self) {
@@ -392,6 +904,10 @@ impl VertexArray {
         unsafe { gl::BindVertexArray(0) }
     }
 
+    pub fn set_label(&self, label: &str) {
+        object_label(gl::VERTEX_ARRAY, self.0, label);
+    }
+
     pub fn vertex_attrib_pointer<T>(
         &self,
         idx: types::GLuint,
@@ -422,70 +938,15 @@ impl VertexArray {
     pub fn get_attrib_location(&self, program: &Program, name: &str) -> i32 {
         unsafe {
             let cstr = CString::new(name).unwrap();
-            gl::GetAttribLocation(program.clone().id(), cstr.as_ptr())
+            gl::GetAttribLocation(program.id(), cstr.as_ptr())
         }
     }
 }
 
-pub struct Buffer(pub types::GLuint);
-
-impl Buffer {
-    pub fn new() -> Result<Buffer, String> {
-        let mut buffer = 0;
-        unsafe {
-            gl::GenBuffers(1, &mut buffer);
-        }
-        if buffer != 0 {
-            Ok(Buffer(buffer))
-        } else {
-            let err = String::from("Buffer creation failed");
-            Err(format!("[elara-gfx] {}", err))
-        }
-    }
-
-    pub fn bind(&self, buffer_type: BufferType) {
-        unsafe { gl::BindBuffer(buffer_type as types::GLenum, self.0) }
-    }
-
-    pub fn unbind(&self, buffer_type: BufferType) {
-        unsafe { gl::BindBuffer(buffer_type as types::GLenum, 0) }
-    }
-
-    // TODO: this might be better using generic instead of assuming
-    // data to be an array of f32 values
-    pub fn data<T>(&self, buffer_type: BufferType, data: &[T], usage: types::GLenum) {
-        unsafe {
-            gl::BufferData(
-                buffer_type as types::GLenum,
-                (data.len() * std::mem::size_of::<T>()) as types::GLsizeiptr,
-                std::mem::transmute(&data[0]),
-                usage,
-            )
-        }
-    }
-
-    // Binds a buffer object and allocates memory without copying
-    // data to buffer
-    pub fn data_empty<T>(&self, buffer_type: BufferType, size: usize, usage: types::GLenum) {
-        unsafe {
-            gl::BufferData(
-                buffer_type as types::GLenum,
-                (size * std::mem::size_of::<T>()) as types::GLsizeiptr,
-                std::ptr::null(),
-                usage
-            )
-        }
-    }
-
-    pub fn subdata<T, const N: usize>(&self, buffer_type: BufferType, offset: isize, data: &[T; N]) 
-    {
+impl Drop for VertexArray {
+    fn drop(&mut self) {
         unsafe {
-            gl::BufferSubData(
-                buffer_type as types::GLenum,
-                offset,
-                std::mem::size_of::<[T; N]>() as isize,
-                data.as_ptr() as *const types::c_void
-            )
+            gl::DeleteVertexArrays(1, &self.0);
         }
     }
 }
@@ -493,10 +954,16 @@ impl Buffer {
 pub struct Uniform(pub types::GLint);
 
 impl Uniform {
-    pub fn new(program: &Program, uniform_name: &str) -> Result<Uniform, String> {
-        let uniform_name = CString::new(uniform_name).unwrap();
-        let id = unsafe { gl::GetUniformLocation(program.id(), uniform_name.as_ptr().cast()) };
-        Ok(Uniform(id))
+    // Looks up the location through the program's cache, a
+    // missing uniform gives location -1 which GL ignores
+    pub fn new(program: &Program, uniform_name: &str) -> GfxResult<Uniform> {
+        Ok(Uniform(program.uniform_location(uniform_name).unwrap_or(-1)))
+    }
+
+    // Sets the uniform on the program currently in use,
+    // without any type checking
+    pub fn set<V: UniformValue>(&self, value: V) {
+        value.upload(self.0);
     }
 
     pub fn id(&self) -> types::GLint {
@@ -520,101 +987,117 @@ impl Uniform {
             gl::Uniform3f(self.0, val_a, val_b, val_c);
         }
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum BufferType {
-    Array = gl::ARRAY_BUFFER as isize,
-    ElementArray = gl::ELEMENT_ARRAY_BUFFER as isize,
-    FrameBuffer = gl::FRAMEBUFFER as isize
-}
-
-pub struct FrameBuffer(pub types::GLuint);
-
-impl FrameBuffer {
-    pub fn new() -> Result<FrameBuffer, String> {
-        let mut framebuffer = 0;
+    pub fn uniform4f(&self, val_a: f32, val_b: f32, val_c: f32, val_d: f32) {
         unsafe {
-            gl::GenFramebuffers(1, &mut framebuffer);
-        }
-        if framebuffer != 0 {
-            Ok(FrameBuffer(framebuffer))
-        } else {
-            let err = String::from("Framebuffer creation failed");
-            Err(format!("[elara-gfx] {}", err))
+            gl::Uniform4f(self.0, val_a, val_b, val_c, val_d);
         }
     }
 
-    pub fn bind(&self) {
-        unsafe { 
-            gl::BindFramebuffer(gl::FRAMEBUFFER, self.0);
-        }
-    }
-    
-    pub fn set_renderbuffer(&self, render_buffer: RenderBuffer) {
+    pub fn uniform1i(&self, val: i32) {
         unsafe {
-            gl::FramebufferRenderbuffer(
-                gl::DRAW_FRAMEBUFFER,
-                gl::COLOR_ATTACHMENT0,
-                gl::RENDERBUFFER,
-                render_buffer.id()
-            )
+            gl::Uniform1i(self.0, val);
         }
     }
-    
-    pub fn set_color_buffer(&self) {
+
+    pub fn uniform1ui(&self, val: u32) {
         unsafe {
-            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            gl::Uniform1ui(self.0, val);
         }
     }
 }
 
-pub struct RenderBuffer(pub types::GLuint);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferType {
+    Array = gl::ARRAY_BUFFER as isize,
+    ElementArray = gl::ELEMENT_ARRAY_BUFFER as isize,
+    FrameBuffer = gl::FRAMEBUFFER as isize,
+    CopyRead = gl::COPY_READ_BUFFER as isize,
+    CopyWrite = gl::COPY_WRITE_BUFFER as isize,
+    Uniform = gl::UNIFORM_BUFFER as isize,
+    ShaderStorage = gl::SHADER_STORAGE_BUFFER as isize,
+    DispatchIndirect = gl::DISPATCH_INDIRECT_BUFFER as isize,
+}
 
-impl RenderBuffer {
-    pub fn new() -> Result<RenderBuffer, String> {
-        let mut render_buffer = 0;
-        unsafe {
-            gl::GenRenderbuffers(1, &mut render_buffer);
-        }
-        if render_buffer != 0 {
-            Ok(RenderBuffer(render_buffer))
-        } else {
-            let err = String::from("Framebuffer creation failed");
-            Err(format!("[elara-gfx] {}", err))
-        }
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+    Vertex = gl::VERTEX_SHADER as isize,
+    Fragment = gl::FRAGMENT_SHADER as isize,
+    // Requires GL 3.2
+    Geometry = gl::GEOMETRY_SHADER as isize,
+    // Tessellation requires GL 4.0
+    TessControl = gl::TESS_CONTROL_SHADER as isize,
+    TessEvaluation = gl::TESS_EVALUATION_SHADER as isize,
+    // Requires GL 4.3
+    Compute = gl::COMPUTE_SHADER as isize,
+}
 
-    pub fn bind(&self) {
-        unsafe { 
-            gl::BindRenderbuffer(gl::RENDERBUFFER, self.0);
+impl ShaderStage {
+    pub fn from_gl(shader_type: types::GLenum) -> Option<ShaderStage> {
+        match shader_type {
+            gl::VERTEX_SHADER => Some(ShaderStage::Vertex),
+            gl::FRAGMENT_SHADER => Some(ShaderStage::Fragment),
+            gl::GEOMETRY_SHADER => Some(ShaderStage::Geometry),
+            gl::TESS_CONTROL_SHADER => Some(ShaderStage::TessControl),
+            gl::TESS_EVALUATION_SHADER => Some(ShaderStage::TessEvaluation),
+            gl::COMPUTE_SHADER => Some(ShaderStage::Compute),
+            _ => None,
         }
     }
-    
-    pub fn id(&self) -> u32 {
-        self.0
-    }
-    
-    pub fn set_storage(&self, width: i32, height: i32) {
-        unsafe {
-            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::RGB565, width, height);
+
+    // Lowercase name used in logs and errors
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShaderStage::Vertex => "vertex",
+            ShaderStage::Fragment => "fragment",
+            ShaderStage::Geometry => "geometry",
+            ShaderStage::TessControl => "tessellation control",
+            ShaderStage::TessEvaluation => "tessellation evaluation",
+            ShaderStage::Compute => "compute",
         }
     }
 }
 
+#[derive(Debug)]
 pub struct Shader {
     id: types::GLuint,
+    stage: ShaderStage,
 }
 
 impl Shader {
-    pub fn new(source: &str, shader_type: types::GLenum) -> Result<Shader, String> {
-        let id = create_shader(source, shader_type)?;
-        Ok(Shader { id })
+    pub fn new(source: &str, stage: ShaderStage) -> GfxResult<Shader> {
+        let id = create_shader(source, stage)?;
+        Ok(Shader { id, stage })
+    }
+
+    // Preprocesses `source` and compiles it; locations in compile
+    // errors refer to the original files and lines
+    pub fn from_source(source: &ShaderSource, stage: ShaderStage) -> GfxResult<Shader> {
+        Shader::from_preprocessed(&source.preprocess()?, stage)
+    }
+
+    pub fn from_preprocessed(preprocessed: &PreprocessedSource, stage: ShaderStage) -> GfxResult<Shader> {
+        match create_shader(&preprocessed.code, stage) {
+            Ok(id) => Ok(Shader { id, stage }),
+            Err(GfxError::ShaderCompileError { stage, log, mut diagnostics }) => {
+                preprocessed.map_diagnostics(&mut diagnostics);
+                Err(GfxError::ShaderCompileError { stage, log: preprocessed.map_log(&log), diagnostics })
+            }
+            Err(err) => Err(err),
+        }
     }
 
     pub fn id(&self) -> types::GLuint {
         self.id
     }
+
+    pub fn stage(&self) -> ShaderStage {
+        self.stage
+    }
+
+    pub fn set_label(&self, label: &str) {
+        object_label(gl::SHADER, self.id, label);
+    }
 }
 
 impl Drop for Shader {
@@ -625,15 +1108,157 @@ impl Drop for Shader {
     }
 }
 
-#[derive(Clone)]
+#[derive(Debug)]
 pub struct Program {
     id: types::GLuint,
+    uniforms: HashMap<String, UniformInfo>,
+    uniform_blocks: HashMap<String, UniformBlockInfo>,
+    attributes: HashMap<String, AttributeInfo>,
+    // Locations looked up by name, including misses (-1)
+    // so that missing uniforms are only reported once
+    locations: RefCell<HashMap<String, types::GLint>>,
 }
 
 impl Program {
-    pub fn new(shaders: &[Shader]) -> Result<Program, String> {
-        let id = create_program(shaders)?;
-        Ok(Program { id })
+    // Links any valid combination of stages: vertex with optional
+    // tessellation, geometry and fragment shaders, or a single
+    // compute shader
+    pub fn new(shaders: &[Shader]) -> GfxResult<Program> {
+        let stages: Vec<ShaderStage> = shaders.iter().map(|shader| shader.stage()).collect();
+        check_stages(&stages)?;
+        Ok(Program::from_linked(create_program(shaders, false)?))
+    }
+
+    // Wraps a successfully linked program object
+    pub(crate) fn from_linked(id: types::GLuint) -> Program {
+        Program {
+            id,
+            uniforms: uniform::reflect_uniforms(id),
+            uniform_blocks: uniform::reflect_uniform_blocks(id),
+            attributes: uniform::reflect_attributes(id),
+            locations: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // Compiles each `(stage, source)` pair and links them; a
+    // compile error reports the stage that failed
+    pub fn from_sources(sources: &[(ShaderStage, &str)]) -> GfxResult<Program> {
+        let shaders = sources
+            .iter()
+            .map(|&(stage, source)| Shader::new(source, stage))
+            .collect::<GfxResult<Vec<Shader>>>()?;
+        Program::new(&shaders)
+    }
+
+    // Active uniforms, i.e. the ones the GLSL compiler did
+    // not optimize out
+    pub fn uniforms(&self) -> impl Iterator<Item = &UniformInfo> {
+        self.uniforms.values()
+    }
+
+    pub fn uniform_info(&self, name: &str) -> Option<&UniformInfo> {
+        self.uniforms.get(name)
+    }
+
+    pub fn uniform_blocks(&self) -> impl Iterator<Item = &UniformBlockInfo> {
+        self.uniform_blocks.values()
+    }
+
+    pub fn uniform_block_info(&self, name: &str) -> Option<&UniformBlockInfo> {
+        self.uniform_blocks.get(name)
+    }
+
+    pub fn attributes(&self) -> impl Iterator<Item = &AttributeInfo> {
+        self.attributes.values()
+    }
+
+    pub fn attribute_info(&self, name: &str) -> Option<&AttributeInfo> {
+        self.attributes.get(name)
+    }
+
+    // Cached uniform location, `name` may also be an array
+    // element like `lights[2]`; warns once per missing name
+    pub fn uniform_location(&self, name: &str) -> Option<types::GLint> {
+        let mut locations = self.locations.borrow_mut();
+        let location = *locations.entry(name.to_string()).or_insert_with(|| {
+            let location = match self.uniforms.get(name) {
+                Some(info) => info.location,
+                None => CString::new(name)
+                    .map(|cname| unsafe { gl::GetUniformLocation(self.id, cname.as_ptr()) })
+                    .unwrap_or(-1),
+            };
+            if location < 0 {
+                warn!("[elara-gfx] Uniform `{}` is not active in program {}, it may have been optimized out", name, self.id);
+            }
+            location
+        });
+        (location >= 0).then_some(location)
+    }
+
+    // Uses the program and sets uniform `name`, checking `value`
+    // against the uniform's type; setting a uniform that is not
+    // active only logs a warning since compilers drop unused ones
+    pub fn set<V: UniformValue>(&self, name: &str, value: V) -> GfxResult<()> {
+        let location = match self.uniform_location(name) {
+            Some(location) => location,
+            None => return Ok(()),
+        };
+        let array_base = name.split('[').next().unwrap_or(name);
+        if let Some(info) = self.uniforms.get(array_base) {
+            if !V::matches(info.gl_type) || (value.count() > 1 && info.size == 1) {
+                return Err(GfxError::UniformTypeMismatch {
+                    name: name.to_string(),
+                    expected: glsl_type_name(info.gl_type),
+                    found: V::glsl_type(),
+                });
+            }
+            // GL silently drops values past the end of the array
+            let first = name[array_base.len()..]
+                .strip_prefix('[')
+                .and_then(|rest| rest.split(']').next())
+                .and_then(|index| index.parse::<usize>().ok())
+                .unwrap_or(0);
+            let available = (info.size.max(0) as usize).saturating_sub(first);
+            if value.count() > available {
+                return Err(GfxError::UniformArrayOverflow {
+                    name: name.to_string(),
+                    available,
+                    found: value.count(),
+                });
+            }
+        }
+        self.use_program();
+        value.upload(location);
+        Ok(())
+    }
+
+    // Assigns uniform block `name` to binding point `binding`;
+    // like `set()`, a block that is not active only logs a warning
+    pub fn set_uniform_block_binding(&self, name: &str, binding: u32) {
+        match self.uniform_blocks.get(name) {
+            Some(block) => unsafe { gl::UniformBlockBinding(self.id, block.index, binding) },
+            None => warn!("[elara-gfx] Uniform block `{}` is not active in program {}", name, self.id),
+        }
+    }
+
+    // Checks that `buffer` covers block `name`, assigns the block
+    // to `binding` and binds the buffer there. Programs sharing a
+    // block just bind the same buffer to the same point. Drivers
+    // disagree on the block's trailing padding, so a buffer may
+    // be larger than the size they report.
+    pub fn bind_uniform_buffer<T: Std140>(&self, name: &str, buffer: &UniformBuffer<T>, binding: u32) -> GfxResult<()> {
+        if let Some(block) = self.uniform_blocks.get(name) {
+            if buffer.size_bytes() < block.size {
+                return Err(GfxError::UniformBlockSizeMismatch {
+                    name: name.to_string(),
+                    expected: block.size,
+                    found: buffer.size_bytes(),
+                });
+            }
+        }
+        self.set_uniform_block_binding(name, binding);
+        buffer.bind_base(binding);
+        Ok(())
     }
 
     pub fn use_program(&self) {
@@ -656,7 +1281,13 @@ impl Program {
         self.id
     }
 
-    pub fn delete(&self) {
+    pub fn set_label(&self, label: &str) {
+        object_label(gl::PROGRAM, self.id, label);
+    }
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
         unsafe {
             gl::DeleteProgram(self.id);
         }
@@ -713,8 +1344,30 @@ pub fn create_fbo() -> types::GLuint {
     fbo
 }
 
-fn create_shader(source: &str, shader_type: types::GLenum) -> Result<types::GLuint, String> {
-    let id = unsafe { gl::CreateShader(shader_type) };
+// Rejects stage combinations that can never link, so the
+// error says what is wrong instead of an opaque link log
+pub(crate) fn check_stages(stages: &[ShaderStage]) -> GfxResult<()> {
+    let has = |stage| stages.contains(&stage);
+    if stages.is_empty() {
+        Err(GfxError::InvalidShaderStages("a program needs at least one shader"))
+    } else if has(ShaderStage::Compute) && stages.iter().any(|&stage| stage != ShaderStage::Compute) {
+        Err(GfxError::InvalidShaderStages("compute shaders can't be linked with other stages"))
+    } else if has(ShaderStage::TessControl) && !has(ShaderStage::TessEvaluation) {
+        Err(GfxError::InvalidShaderStages("a tessellation control shader needs a tessellation evaluation shader"))
+    } else if !has(ShaderStage::Vertex)
+        && (has(ShaderStage::Geometry) || has(ShaderStage::TessControl) || has(ShaderStage::TessEvaluation))
+    {
+        Err(GfxError::InvalidShaderStages("geometry and tessellation shaders need a vertex shader"))
+    } else {
+        Ok(())
+    }
+}
+
+fn create_shader(source: &str, stage: ShaderStage) -> GfxResult<types::GLuint> {
+    let id = unsafe { gl::CreateShader(stage as types::GLenum) };
+    if id == 0 {
+        return Err(GfxError::ObjectCreationError("Shader"));
+    }
     unsafe {
         gl::ShaderSource(
             id,
@@ -728,29 +1381,46 @@ fn create_shader(source: &str, shader_type: types::GLenum) -> Result<types::GLui
         gl::GetShaderiv(id, gl::COMPILE_STATUS, &mut success);
 
         if success == 0 {
-            if shader_type == gl::VERTEX_SHADER {
-                warn!("[elara-gfx] Vertex shader compilation failed");
-            } else {
-                warn!("[elara-gfx] Fragment shader compilation failed");
+            warn!("[elara-gfx] Compiling the {} shader failed", stage.name());
+            let log = info_log(id, gl::GetShaderiv, gl::GetShaderInfoLog);
+            gl::DeleteShader(id);
+            let mut diagnostics = parse_info_log(&log);
+            for diagnostic in &mut diagnostics {
+                diagnostic.attach_excerpt(source);
             }
-            let mut log_len = 0_i32;
-            let mut error: Vec<u8> = Vec::with_capacity(gl::INFO_LOG_LENGTH as usize);
-            gl::GetShaderInfoLog(
-                id,
-                gl::INFO_LOG_LENGTH as i32,
-                &mut log_len,
-                error.as_mut_ptr().cast(),
-            );
-            error.set_len(log_len.try_into().unwrap());
-            let error_msg = String::from_utf8_lossy(&error);
-            return Err(error_msg.to_string());
+            return Err(GfxError::ShaderCompileError { stage, log, diagnostics });
         }
         Ok(id)
     }
 }
 
-fn create_program(shaders: &[Shader]) -> Result<types::GLuint, String> {
+// Reads the info log of a shader or program, sized by
+// what the driver reports for INFO_LOG_LENGTH
+unsafe fn info_log(
+    id: types::GLuint,
+    get_iv: unsafe fn(types::GLuint, types::GLenum, *mut types::GLint),
+    get_log: unsafe fn(types::GLuint, types::GLsizei, *mut types::GLsizei, *mut types::GLchar),
+) -> String {
+    let mut capacity = 0;
+    get_iv(id, gl::INFO_LOG_LENGTH, &mut capacity);
+    let mut log: Vec<u8> = vec![0; capacity.max(1) as usize];
+    let mut len = 0;
+    get_log(id, log.len() as types::GLsizei, &mut len, log.as_mut_ptr().cast());
+    log.truncate(len.max(0) as usize);
+    String::from_utf8_lossy(&log).into_owned()
+}
+
+// `retrievable` asks the driver to keep the binary around
+// for glGetProgramBinary, only pass it when the context
+// supports program binaries
+pub(crate) fn create_program(shaders: &[Shader], retrievable: bool) -> GfxResult<types::GLuint> {
     let id = unsafe { gl::CreateProgram() };
+    if id == 0 {
+        return Err(GfxError::ObjectCreationError("Program"));
+    }
+    if retrievable {
+        unsafe { gl::ProgramParameteri(id, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as types::GLint) }
+    }
     for shader in shaders {
         unsafe { gl::AttachShader(id, shader.id()) }
     }
@@ -765,17 +1435,12 @@ fn create_program(shaders: &[Shader]) -> Result<types::GLuint, String> {
 
         if success == 0 {
             warn!("[elara-gfx] Program compilation failed");
-            let mut log_len = 0_i32;
-            let mut error: Vec<u8> = Vec::with_capacity(gl::INFO_LOG_LENGTH as usize);
-            gl::GetProgramInfoLog(
-                id,
-                gl::INFO_LOG_LENGTH as i32,
-                &mut log_len,
-                error.as_mut_ptr().cast(),
-            );
-            error.set_len(log_len.try_into().unwrap());
-            let error_msg = String::from_utf8_lossy(&error);
-            return Err(error_msg.to_string());
+            let log = info_log(id, gl::GetProgramiv, gl::GetProgramInfoLog);
+            gl::DeleteProgram(id);
+            // Link errors don't say which shader a line is in, so
+            // there is no source to quote
+            let diagnostics = parse_info_log(&log);
+            return Err(GfxError::ProgramLinkError { log, diagnostics });
         }
 
         for shader in shaders {
@@ -0,0 +1,374 @@
+//! GLSL preprocessing: `#include`, defines and version injection
+//!
+//! Drivers don't support `#include`, so `ShaderSource` expands
+//! includes before compiling and remembers where every line
+//! came from, so that compile errors point at the original
+//! file and line:
+//!
+//! ```ignore
+//! let includes = FileIncludeResolver::new("examples/shaders");
+//! let source = ShaderSource::new("circle.frag", &src)
+//!     .version("330 core")
+//!     .define("SAMPLES", 4)
+//!     .includes(&includes);
+//! let shader = Shader::from_source(&source, ShaderStage::Fragment)?;
+//! ```
+use crate::diagnostics::{parse_log_location, ShaderDiagnostic};
+use crate::{GfxError, GfxResult};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::path::PathBuf;
+
+// Looks up the files named in `#include` directives
+pub trait IncludeResolver {
+    // Contents of `path`, or `None` if there is no such file
+    fn resolve(&self, path: &str) -> Option<String>;
+}
+
+impl<F: Fn(&str) -> Option<String>> IncludeResolver for F {
+    fn resolve(&self, path: &str) -> Option<String> {
+        self(path)
+    }
+}
+
+// Resolves includes relative to a directory
+#[derive(Debug, Clone)]
+pub struct FileIncludeResolver {
+    root: PathBuf,
+}
+
+impl FileIncludeResolver {
+    pub fn new(root: impl Into<PathBuf>) -> FileIncludeResolver {
+        FileIncludeResolver { root: root.into() }
+    }
+}
+
+impl IncludeResolver for FileIncludeResolver {
+    fn resolve(&self, path: &str) -> Option<String> {
+        std::fs::read_to_string(self.root.join(path)).ok()
+    }
+}
+
+// Resolves includes from files compiled into the binary, e.g.
+// with `include_str!()`, or generated at runtime
+#[derive(Debug, Clone, Default)]
+pub struct EmbeddedIncludeResolver {
+    files: HashMap<String, Cow<'static, str>>,
+}
+
+impl EmbeddedIncludeResolver {
+    pub fn new() -> EmbeddedIncludeResolver {
+        EmbeddedIncludeResolver::default()
+    }
+
+    pub fn file(mut self, path: impl Into<String>, contents: impl Into<Cow<'static, str>>) -> Self {
+        self.files.insert(path.into(), contents.into());
+        self
+    }
+}
+
+impl IncludeResolver for EmbeddedIncludeResolver {
+    fn resolve(&self, path: &str) -> Option<String> {
+        self.files.get(path).map(|contents| contents.to_string())
+    }
+}
+
+// A shader source along with how to preprocess it
+pub struct ShaderSource<'a> {
+    name: String,
+    source: &'a str,
+    version: Option<String>,
+    defines: Vec<(String, String)>,
+    resolver: Option<&'a dyn IncludeResolver>,
+}
+
+impl<'a> ShaderSource<'a> {
+    // `name` is used for the source in error messages
+    pub fn new(name: impl Into<String>, source: &'a str) -> ShaderSource<'a> {
+        ShaderSource { name: name.into(), source, version: None, defines: Vec::new(), resolver: None }
+    }
+
+    // Replaces the source's `#version` directive, or adds one;
+    // takes what follows `#version`, e.g. "330 core" or "300 es"
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    // Adds `#define name value` right after the version directive
+    pub fn define(mut self, name: impl Into<String>, value: impl Display) -> Self {
+        self.defines.push((name.into(), value.to_string()));
+        self
+    }
+
+    // Without a resolver, any `#include` is an error
+    pub fn includes(mut self, resolver: &'a dyn IncludeResolver) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn preprocess(&self) -> GfxResult<PreprocessedSource> {
+        let mut out = PreprocessedSource { code: String::new(), files: vec![self.name.clone()], lines: Vec::new() };
+
+        // The version must come first, so pull it out of the
+        // source before expanding anything
+        let mut version_line = None;
+        for (i, line) in self.source.lines().enumerate() {
+            if let Some(version) = directive(line, "version") {
+                version_line = Some((i, version.to_string()));
+                break;
+            }
+        }
+        let version = self.version.clone().or_else(|| version_line.as_ref().map(|(_, version)| version.clone()));
+        if let Some(version) = version {
+            let origin = version_line.as_ref().map(|&(i, _)| (0, i + 1));
+            out.push_line(&format!("#version {}", version), origin);
+        }
+        for (name, value) in &self.defines {
+            out.push_line(&format!("#define {} {}", name, value), None);
+        }
+
+        let mut state = IncludeState { stack: vec![self.name.clone()], once: HashSet::new() };
+        self.expand(&mut out, &mut state, 0, self.source, version_line.map(|(i, _)| i))?;
+        Ok(out)
+    }
+
+    fn expand(
+        &self,
+        out: &mut PreprocessedSource,
+        state: &mut IncludeState,
+        file: usize,
+        source: &str,
+        skip_line: Option<usize>,
+    ) -> GfxResult<()> {
+        for (i, line) in source.lines().enumerate() {
+            // Only the root file's version counts, and it has already
+            // been emitted; blank lines keep the numbering in sync
+            if Some(i) == skip_line || (file != 0 && directive(line, "version").is_some()) {
+                out.push_line("", Some((file, i + 1)));
+                continue;
+            }
+            if directive(line, "pragma").map(str::trim) == Some("once") {
+                state.once.insert(out.files[file].clone());
+                out.push_line("", Some((file, i + 1)));
+                continue;
+            }
+            let path = match directive(line, "include") {
+                Some(argument) => include_path(argument),
+                None => {
+                    out.push_line(line, Some((file, i + 1)));
+                    continue;
+                }
+            };
+
+            let from = out.files[file].clone();
+            let error = |reason| GfxError::IncludeError { path: path.unwrap_or(line.trim()).to_string(), from: from.clone(), line: i + 1, reason };
+            let path = path.ok_or_else(|| error("expected a path in quotes or angle brackets"))?;
+            let resolver = self.resolver.ok_or_else(|| error("no include resolver was given"))?;
+            // Look next to the including file first
+            let mut candidates = vec![path.to_string()];
+            if let Some(slash) = from.rfind('/') {
+                candidates.insert(0, format!("{}/{}", &from[..slash], path));
+            }
+            let (resolved, contents) = candidates
+                .into_iter()
+                .find_map(|candidate| resolver.resolve(&candidate).map(|contents| (candidate, contents)))
+                .ok_or_else(|| error("file not found"))?;
+            // A `#pragma once` file including itself again
+            // through another file is skipped, not a cycle
+            if state.once.contains(&resolved) {
+                out.push_line("", Some((file, i + 1)));
+                continue;
+            }
+            if state.stack.contains(&resolved) {
+                return Err(error("circular include"));
+            }
+
+            out.files.push(resolved.clone());
+            state.stack.push(resolved);
+            let included = out.files.len() - 1;
+            self.expand(out, state, included, &contents, None)?;
+            state.stack.pop();
+        }
+        Ok(())
+    }
+}
+
+struct IncludeState {
+    // Files currently being expanded, to catch cycles
+    stack: Vec<String>,
+    // Files that contained `#pragma once`
+    once: HashSet<String>,
+}
+
+// The argument of `#name` if `line` is that directive
+fn directive<'l>(line: &'l str, name: &str) -> Option<&'l str> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start().strip_prefix(name)?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then(|| rest.trim())
+}
+
+// `"path"` or `<path>`
+fn include_path(argument: &str) -> Option<&str> {
+    argument
+        .strip_prefix('"')
+        .and_then(|rest| rest.split_once('"'))
+        .or_else(|| argument.strip_prefix('<').and_then(|rest| rest.split_once('>')))
+        .map(|(path, _)| path)
+}
+
+// The expanded code and where each of its lines came from
+#[derive(Debug, Clone)]
+pub struct PreprocessedSource {
+    pub code: String,
+    files: Vec<String>,
+    // (file index, 1-based line) per output line, `None`
+    // for injected lines such as defines
+    lines: Vec<Option<(usize, usize)>>,
+}
+
+impl PreprocessedSource {
+    fn push_line(&mut self, line: &str, origin: Option<(usize, usize)>) {
+        self.code.push_str(line);
+        self.code.push('\n');
+        self.lines.push(origin);
+    }
+
+    // Names of the files the code was expanded from, starting
+    // with the source itself
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    // File name and line that line `line` (1-based) of the
+    // expanded code came from
+    pub fn original_location(&self, line: usize) -> Option<(&str, usize)> {
+        let (file, line) = (*self.lines.get(line.checked_sub(1)?)?)?;
+        Some((&self.files[file], line))
+    }
+
+    // Rewrites the locations in a driver's info log to refer
+    // to the original files, e.g. `0:12(5): error` becomes
+    // `common.glsl:3(5): error`
+    pub fn map_log(&self, log: &str) -> String {
+        let mut mapped = String::with_capacity(log.len());
+        for line in log.lines() {
+            match parse_log_location(line) {
+                Some(location) => match self.original_location(location.line) {
+                    Some((file, original)) => {
+                        mapped.push_str(&line[..location.start]);
+                        mapped.push_str(file);
+                        mapped.push_str(&location.format_line(original));
+                        mapped.push_str(&line[location.end..]);
+                    }
+                    None => mapped.push_str(line),
+                },
+                None => mapped.push_str(line),
+            }
+            mapped.push('\n');
+        }
+        mapped
+    }
+    // Points diagnostics parsed from the log of this code at
+    // the original files and lines, dropping excerpt lines
+    // that came from a different file
+    pub fn map_diagnostics(&self, diagnostics: &mut [ShaderDiagnostic]) {
+        for diagnostic in diagnostics {
+            let (file, line) = match diagnostic.line.and_then(|line| self.original_location(line)) {
+                Some(location) => location,
+                None => continue,
+            };
+            diagnostic.excerpt = diagnostic
+                .excerpt
+                .iter()
+                .filter_map(|(number, text)| match self.original_location(*number) {
+                    Some((excerpt_file, original)) if excerpt_file == file => Some((original, text.clone())),
+                    _ => None,
+                })
+                .collect();
+            diagnostic.file = Some(file.to_string());
+            diagnostic.line = Some(line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn includes() -> EmbeddedIncludeResolver {
+        EmbeddedIncludeResolver::new()
+            .file("lib/common.glsl", "#include \"math.glsl\"\nfloat common_fn() { return PI; }\n")
+            .file("lib/math.glsl", "#pragma once\nconst float PI = 3.14159;\n")
+            .file("a.glsl", "#include \"b.glsl\"\n")
+            .file("b.glsl", "#include \"a.glsl\"\n")
+            .file("left.glsl", "#pragma once\n#include \"shared.glsl\"\nfloat left() { return shared(); }\n")
+            .file("right.glsl", "#include \"shared.glsl\"\nfloat right() { return shared(); }\n")
+            .file("shared.glsl", "#pragma once\n#include \"left.glsl\"\nfloat shared() { return 1.0; }\n")
+    }
+
+    #[test]
+    fn expands_nested_includes() {
+        let resolver = includes();
+        let source = "#version 330 core\n#include \"lib/common.glsl\"\n#include \"lib/math.glsl\"\nvoid main() {}\n";
+        let out = ShaderSource::new("main.frag", source).includes(&resolver).preprocess().unwrap();
+        let lines: Vec<&str> = out.code.lines().collect();
+        // `math.glsl` is found next to `common.glsl`, and its
+        // second include is dropped by `#pragma once`
+        assert_eq!(lines.iter().filter(|line| line.contains("const float PI")).count(), 1);
+        assert_eq!(out.files(), ["main.frag", "lib/common.glsl", "lib/math.glsl"]);
+        let pi = lines.iter().position(|line| line.contains("const float PI")).unwrap() + 1;
+        assert_eq!(out.original_location(pi), Some(("lib/math.glsl", 2)));
+        let common = lines.iter().position(|line| line.contains("common_fn")).unwrap() + 1;
+        assert_eq!(out.original_location(common), Some(("lib/common.glsl", 2)));
+        let main = lines.iter().position(|line| line.contains("void main")).unwrap() + 1;
+        assert_eq!(out.original_location(main), Some(("main.frag", 4)));
+    }
+
+    #[test]
+    fn rejects_include_cycles() {
+        let resolver = includes();
+        let err = ShaderSource::new("main.frag", "#include \"a.glsl\"\n").includes(&resolver).preprocess().unwrap_err();
+        match err {
+            GfxError::IncludeError { path, from, line, reason } => {
+                assert_eq!((path.as_str(), from.as_str(), line, reason), ("a.glsl", "b.glsl", 1, "circular include"));
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn skips_pragma_once_files_in_include_diamond() {
+        let resolver = includes();
+        // `shared.glsl` is included by both sides of the diamond
+        // and includes `left.glsl` back, which is skipped by its
+        // `#pragma once` instead of being reported as a cycle
+        let source = "#include \"left.glsl\"\n#include \"right.glsl\"\n";
+        let out = ShaderSource::new("main.frag", source).includes(&resolver).preprocess().unwrap();
+        let lines: Vec<&str> = out.code.lines().collect();
+        assert_eq!(lines.iter().filter(|line| line.contains("float shared()")).count(), 1);
+        assert_eq!(out.files(), ["main.frag", "left.glsl", "shared.glsl", "right.glsl"]);
+        let shared = lines.iter().position(|line| line.contains("float shared()")).unwrap() + 1;
+        assert_eq!(out.original_location(shared), Some(("shared.glsl", 3)));
+        let right = lines.iter().position(|line| line.contains("float right()")).unwrap() + 1;
+        assert_eq!(out.original_location(right), Some(("right.glsl", 2)));
+    }
+
+    #[test]
+    fn moves_version_to_the_top() {
+        let source = "// Header comment\n#version 330 core\nout vec4 f;\n";
+        let out = ShaderSource::new("main.frag", source).define("SAMPLES", 4).preprocess().unwrap();
+        let lines: Vec<&str> = out.code.lines().collect();
+        assert_eq!(lines[0], "#version 330 core");
+        assert_eq!(lines[1], "#define SAMPLES 4");
+        assert_eq!(out.original_location(1), Some(("main.frag", 2)));
+        assert_eq!(out.original_location(2), None);
+        assert_eq!(out.original_location(3), Some(("main.frag", 1)));
+        assert_eq!(out.original_location(5), Some(("main.frag", 3)));
+        assert_eq!(out.map_log("0:5(10): error: `f' redeclared\n"), "main.frag:3(10): error: `f' redeclared\n");
+    }
+}
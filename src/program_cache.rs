@@ -0,0 +1,201 @@
+//! On-disk cache of linked program binaries
+//!
+//! Compiling large shaders is slow on some drivers, llvmpipe
+//! in particular. A `ProgramCache` stores what
+//! `glGetProgramBinary` returns for a set of sources and loads
+//! it back with `glProgramBinary` on later runs:
+//!
+//! ```ignore
+//! let cache = ProgramCache::new("target/shader-cache");
+//! let program = Program::from_sources_cached(&[
+//!     (ShaderStage::Vertex, VERT_SHADER),
+//!     (ShaderStage::Fragment, FRAG_SHADER),
+//! ], &cache)?;
+//! ```
+//!
+//! Entries are keyed by the sources and the renderer, vendor
+//! and version strings, so a driver update invalidates them.
+//! Binaries the driver rejects are deleted and rebuilt from
+//! source.
+use crate::{check_stages, create_program, gl_get_string, types, GfxResult, GlCapabilities, Program, Shader, ShaderStage};
+use elara_log::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Start of every cache file, followed by the binary format
+// and the binary itself
+const MAGIC: &[u8; 8] = b"ELGFXPB1";
+
+#[derive(Debug, Clone)]
+pub struct ProgramCache {
+    dir: PathBuf,
+}
+
+impl ProgramCache {
+    // The directory is created when the first binary is stored
+    pub fn new(dir: impl Into<PathBuf>) -> ProgramCache {
+        ProgramCache { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Whether the current context can save and load program
+    // binaries at all; without it the cache does nothing
+    pub fn is_supported() -> bool {
+        if !GlCapabilities::query().supports_program_binary() {
+            return false;
+        }
+        let mut formats = 0;
+        unsafe {
+            gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats);
+        }
+        formats > 0
+    }
+
+    // Deletes every cached binary
+    pub fn clear(&self) -> std::io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "bin") {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Loads the program built from `sources`, or calls `build`
+    // to compile the shaders and links and stores them
+    pub(crate) fn load_or_build(
+        &self,
+        sources: &[(ShaderStage, &str)],
+        build: impl FnOnce() -> GfxResult<Vec<Shader>>,
+    ) -> GfxResult<Program> {
+        let stages: Vec<ShaderStage> = sources.iter().map(|&(stage, _)| stage).collect();
+        check_stages(&stages)?;
+        if !ProgramCache::is_supported() {
+            return Program::new(&build()?);
+        }
+        let path = self.dir.join(format!("{:016x}.bin", cache_key(sources)));
+        if let Some(id) = load_binary(&path) {
+            debug!("[elara-gfx] Loaded program {} from {}", id, path.display());
+            return Ok(Program::from_linked(id));
+        }
+
+        let shaders = build()?;
+        let id = create_program(&shaders, true)?;
+        if let Err(err) = store_binary(&path, id) {
+            warn!("[elara-gfx] Cannot write program cache {}: {}", path.display(), err);
+        }
+        Ok(Program::from_linked(id))
+    }
+}
+
+impl Program {
+    // Like from_sources(), but reuses the binary `cache` holds
+    // for these sources instead of compiling them if it can
+    pub fn from_sources_cached(sources: &[(ShaderStage, &str)], cache: &ProgramCache) -> GfxResult<Program> {
+        cache.load_or_build(sources, || {
+            sources.iter().map(|&(stage, source)| Shader::new(source, stage)).collect()
+        })
+    }
+}
+
+// FNV-1a, since std's hasher isn't guaranteed to be stable
+// between Rust versions
+fn cache_key(sources: &[(ShaderStage, &str)]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |bytes: &[u8]| {
+        // Length first, so that ("ab", "c") and ("a", "bc") differ
+        for byte in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    for name in [gl::VENDOR, gl::RENDERER, gl::VERSION] {
+        feed(gl_get_string(name).unwrap_or("").as_bytes());
+    }
+    for (stage, source) in sources {
+        feed(stage.name().as_bytes());
+        feed(source.as_bytes());
+    }
+    hash
+}
+
+fn load_binary(path: &Path) -> Option<types::GLuint> {
+    let data = fs::read(path).ok()?;
+    if data.len() < MAGIC.len() + 4 || &data[..MAGIC.len()] != MAGIC {
+        warn!("[elara-gfx] Ignoring malformed program cache {}", path.display());
+        let _ = fs::remove_file(path);
+        return None;
+    }
+    let (format, binary) = data[MAGIC.len()..].split_at(4);
+    let format = types::GLenum::from_le_bytes(format.try_into().ok()?);
+    // An unknown format would raise GL_INVALID_ENUM
+    if !binary_formats().contains(&format) {
+        let _ = fs::remove_file(path);
+        return None;
+    }
+
+    let id = unsafe { gl::CreateProgram() };
+    if id == 0 {
+        return None;
+    }
+    let mut success = 0;
+    unsafe {
+        gl::ProgramBinary(id, format, binary.as_ptr().cast(), binary.len() as types::GLsizei);
+        gl::GetProgramiv(id, gl::LINK_STATUS, &mut success);
+    }
+    if success == 0 {
+        // Usually a driver update that kept the version string
+        info!("[elara-gfx] Driver rejected program cache {}, rebuilding from source", path.display());
+        unsafe {
+            gl::DeleteProgram(id);
+        }
+        let _ = fs::remove_file(path);
+        return None;
+    }
+    Some(id)
+}
+
+fn binary_formats() -> Vec<types::GLenum> {
+    let mut count = 0;
+    unsafe {
+        gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut count);
+    }
+    let mut formats = vec![0; count.max(0) as usize];
+    if !formats.is_empty() {
+        unsafe {
+            gl::GetIntegerv(gl::PROGRAM_BINARY_FORMATS, formats.as_mut_ptr());
+        }
+    }
+    formats.into_iter().map(|format| format as types::GLenum).collect()
+}
+
+fn store_binary(path: &Path, id: types::GLuint) -> std::io::Result<()> {
+    let mut len = 0;
+    unsafe {
+        gl::GetProgramiv(id, gl::PROGRAM_BINARY_LENGTH, &mut len);
+    }
+    if len <= 0 {
+        return Err(std::io::Error::other("the driver returned an empty binary"));
+    }
+    let mut binary = vec![0_u8; len as usize];
+    let mut written = 0;
+    let mut format = 0;
+    unsafe {
+        gl::GetProgramBinary(id, len, &mut written, &mut format, binary.as_mut_ptr().cast());
+    }
+    binary.truncate(written.max(0) as usize);
+
+    let mut data = Vec::with_capacity(MAGIC.len() + 4 + binary.len());
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&format.to_le_bytes());
+    data.extend_from_slice(&binary);
+    fs::create_dir_all(path.parent().unwrap_or(Path::new("")))?;
+    // Write then rename, so a crash never leaves half a binary
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, &data)?;
+    fs::rename(&tmp, path)
+}
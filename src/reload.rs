@@ -0,0 +1,239 @@
+//! Programs that recompile when their source files change
+//!
+//! Meant for iterating on shaders during development. Call
+//! `poll()` once per frame, outside of any draw:
+//!
+//! ```ignore
+//! let mut program = ReloadableProgram::new(vec![
+//!     (ShaderStage::Vertex, StageSource::embedded("quad.vert", QUAD_VERT)),
+//!     (ShaderStage::Fragment, StageSource::file("examples/shaders/orbits.frag")),
+//! ])?;
+//! // every frame
+//! program.poll();
+//! program.set("u_time", time)?;
+//! ```
+use crate::uniform::copy_uniform_value;
+use crate::{types, FileIncludeResolver, GfxResult, Program, ProgramCache, Shader, ShaderSource, ShaderStage};
+use elara_log::prelude::*;
+use std::ffi::CString;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+// Where the source of one stage comes from
+#[derive(Debug, Clone)]
+pub enum StageSource {
+    // Read from disk and watched, along with its `#include`s
+    // which are resolved relative to the file's directory
+    File(PathBuf),
+    // Compiled into the binary, never reloaded
+    Embedded { name: String, source: String },
+}
+
+impl StageSource {
+    pub fn file(path: impl Into<PathBuf>) -> StageSource {
+        StageSource::File(path.into())
+    }
+
+    pub fn embedded(name: impl Into<String>, source: impl Into<String>) -> StageSource {
+        StageSource::Embedded { name: name.into(), source: source.into() }
+    }
+}
+
+#[derive(Debug)]
+struct WatchedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl WatchedFile {
+    fn new(path: PathBuf) -> WatchedFile {
+        let modified = modified_time(&path);
+        WatchedFile { path, modified }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+// A program rebuilt from its sources whenever one of the
+// watched files changes; dereferences to the current `Program`
+#[derive(Debug)]
+pub struct ReloadableProgram {
+    program: Program,
+    stages: Vec<(ShaderStage, StageSource)>,
+    watched: Vec<WatchedFile>,
+    cache: Option<ProgramCache>,
+    poll_interval: Duration,
+    last_poll: Instant,
+}
+
+impl ReloadableProgram {
+    // Fails if the initial sources don't build, since there
+    // is no working program to fall back to yet
+    pub fn new(stages: Vec<(ShaderStage, StageSource)>) -> GfxResult<ReloadableProgram> {
+        ReloadableProgram::build_with(stages, None)
+    }
+
+    // Like new(), but loads and stores the program binaries
+    // in `cache`, including those of reloaded versions
+    pub fn with_cache(stages: Vec<(ShaderStage, StageSource)>, cache: ProgramCache) -> GfxResult<ReloadableProgram> {
+        ReloadableProgram::build_with(stages, Some(cache))
+    }
+
+    fn build_with(stages: Vec<(ShaderStage, StageSource)>, cache: Option<ProgramCache>) -> GfxResult<ReloadableProgram> {
+        let (program, watched) = build(&stages, cache.as_ref())?;
+        Ok(ReloadableProgram {
+            program,
+            stages,
+            watched,
+            cache,
+            poll_interval: Duration::from_millis(500),
+            last_poll: Instant::now(),
+        })
+    }
+
+    // How often poll() looks at modification times, 500 ms
+    // by default
+    pub fn set_poll_interval(&mut self, interval: Duration) {
+        self.poll_interval = interval;
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    // Files the program is built from, including the ones
+    // pulled in with `#include`
+    pub fn watched_files(&self) -> impl Iterator<Item = &Path> {
+        self.watched.iter().map(|file| file.path.as_path())
+    }
+
+    // Reloads if a watched file changed since the last check;
+    // returns true when a new program was swapped in. Build
+    // errors are logged and the last working program is kept.
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < self.poll_interval {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let mut changed = false;
+        for file in &mut self.watched {
+            let modified = modified_time(&file.path);
+            if modified != file.modified {
+                file.modified = modified;
+                changed = true;
+            }
+        }
+        if !changed {
+            return false;
+        }
+        match self.reload() {
+            Ok(()) => true,
+            Err(err) => {
+                error!("[elara-gfx] Reloading program {} failed, keeping the previous version: {}", self.program.id(), err);
+                false
+            }
+        }
+    }
+
+    // Rebuilds the program now and, if that succeeds, swaps it
+    // in with the uniform values and block bindings of the old one
+    pub fn reload(&mut self) -> GfxResult<()> {
+        let (program, watched) = build(&self.stages, self.cache.as_ref())?;
+        restore_uniforms(&self.program, &program);
+        info!("[elara-gfx] Reloaded program {} as program {}", self.program.id(), program.id());
+        self.program = program;
+        self.watched = watched;
+        Ok(())
+    }
+}
+
+impl Deref for ReloadableProgram {
+    type Target = Program;
+
+    fn deref(&self) -> &Program {
+        &self.program
+    }
+}
+
+fn build(stages: &[(ShaderStage, StageSource)], cache: Option<&ProgramCache>) -> GfxResult<(Program, Vec<WatchedFile>)> {
+    let mut preprocessed = Vec::with_capacity(stages.len());
+    let mut watched = Vec::new();
+    for (stage, source) in stages {
+        let code = match source {
+            StageSource::File(path) => {
+                let code = std::fs::read_to_string(path)
+                    .map_err(|err| format!("Cannot read shader {}: {}", path.display(), err))?;
+                let dir = path.parent().unwrap_or(Path::new(""));
+                let includes = FileIncludeResolver::new(dir);
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let code = ShaderSource::new(name, &code).includes(&includes).preprocess()?;
+                watched.extend(code.files().iter().map(|file| WatchedFile::new(dir.join(file))));
+                code
+            }
+            StageSource::Embedded { name, source } => ShaderSource::new(name.as_str(), source).preprocess()?,
+        };
+        preprocessed.push((*stage, code));
+    }
+    let compile = || {
+        preprocessed.iter().map(|(stage, code)| Shader::from_preprocessed(code, *stage)).collect::<GfxResult<Vec<Shader>>>()
+    };
+    let program = match cache {
+        Some(cache) => {
+            let sources: Vec<(ShaderStage, &str)> = preprocessed.iter().map(|(stage, code)| (*stage, code.code.as_str())).collect();
+            cache.load_or_build(&sources, compile)?
+        }
+        None => Program::new(&compile()?)?,
+    };
+    Ok((program, watched))
+}
+
+// Copies every uniform that kept its name and type, and
+// the binding points of uniform blocks, from `old` to `new`
+fn restore_uniforms(old: &Program, new: &Program) {
+    let mut current = 0;
+    unsafe {
+        gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut current);
+    }
+    new.use_program();
+    for info in new.uniforms().filter(|info| info.location >= 0) {
+        let old_info = match old.uniform_info(&info.name) {
+            Some(old_info) if old_info.gl_type == info.gl_type && old_info.location >= 0 => old_info,
+            _ => continue,
+        };
+        for element in 0..info.size.min(old_info.size) {
+            let (from, to) = if element == 0 {
+                (old_info.location, info.location)
+            } else {
+                // Not through uniform_location(), drivers may trim
+                // unused elements and that shouldn't warn here
+                let name = match CString::new(format!("{}[{}]", info.name, element)) {
+                    Ok(name) => name,
+                    Err(_) => continue,
+                };
+                let (from, to) = unsafe { (gl::GetUniformLocation(old.id(), name.as_ptr()), gl::GetUniformLocation(new.id(), name.as_ptr())) };
+                if from < 0 || to < 0 {
+                    continue;
+                }
+                (from, to)
+            };
+            copy_uniform_value(old.id(), from, to, info.gl_type);
+        }
+    }
+    for block in new.uniform_blocks() {
+        if let Some(old_block) = old.uniform_block_info(&block.name) {
+            let mut binding = 0;
+            unsafe {
+                gl::GetActiveUniformBlockiv(old.id(), old_block.index, gl::UNIFORM_BLOCK_BINDING, &mut binding);
+            }
+            new.set_uniform_block_binding(&block.name, binding as u32);
+        }
+    }
+    // Keep whatever was in use, unless it was the old program
+    let restored = if current as types::GLuint == old.id() { new.id() } else { current as types::GLuint };
+    unsafe {
+        gl::UseProgram(restored);
+    }
+}
@@ -0,0 +1,294 @@
+//! Sampler state: filtering, wrapping and depth comparison
+//!
+//! A `SamplerDesc` describes how a texture is sampled. It can
+//! be applied to a texture's own parameters, or turned into a
+//! `SamplerObject` bound to a texture unit, which overrides the
+//! parameters of whatever texture is bound to that unit:
+//!
+//! ```ignore
+//! let desc = SamplerDesc::linear().mipmap(MipmapMode::Linear).wrap(Wrap::Repeat);
+//! texture.set_sampler(&desc);
+//!
+//! let shadow = SamplerObject::new(&SamplerDesc::linear().compare(CompareFunc::LessEqual))?;
+//! shadow_map.bind_to_unit(1);
+//! shadow.bind(1);
+//! ```
+use crate::capabilities::{GlCapabilities, TEXTURE_MAX_ANISOTROPY};
+use crate::debug::object_label;
+use crate::{types, GfxError, GfxResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+// How samples from neighbouring mip levels are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MipmapMode {
+    // Only level 0 is sampled, mipmaps aren't needed
+    None,
+    // The closest level
+    Nearest,
+    // A blend of the two closest levels
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat = gl::REPEAT as isize,
+    MirroredRepeat = gl::MIRRORED_REPEAT as isize,
+    ClampToEdge = gl::CLAMP_TO_EDGE as isize,
+    // Samples outside the texture read the border color
+    ClampToBorder = gl::CLAMP_TO_BORDER as isize,
+    MirrorClampToEdge = gl::MIRROR_CLAMP_TO_EDGE as isize,
+}
+
+// Comparison of depth textures sampled through shadow
+// samplers, the reference value is on the left
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareFunc {
+    Never = gl::NEVER as isize,
+    Less = gl::LESS as isize,
+    LessEqual = gl::LEQUAL as isize,
+    Equal = gl::EQUAL as isize,
+    NotEqual = gl::NOTEQUAL as isize,
+    GreaterEqual = gl::GEQUAL as isize,
+    Greater = gl::GREATER as isize,
+    Always = gl::ALWAYS as isize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerDesc {
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    pub mipmap: MipmapMode,
+    // S, T and R axes
+    pub wrap: [Wrap; 3],
+    pub border_color: [f32; 4],
+    // 1 disables anisotropic filtering; clamped to what the
+    // driver supports and ignored without the extension
+    pub max_anisotropy: f32,
+    // Makes shadow samplers compare instead of returning depth
+    pub compare: Option<CompareFunc>,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> SamplerDesc {
+        SamplerDesc::linear()
+    }
+}
+
+impl SamplerDesc {
+    // Bilinear filtering without mipmaps, clamped to the edge
+    pub fn linear() -> SamplerDesc {
+        SamplerDesc {
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Linear,
+            mipmap: MipmapMode::None,
+            wrap: [Wrap::ClampToEdge; 3],
+            border_color: [0.0; 4],
+            max_anisotropy: 1.0,
+            compare: None,
+        }
+    }
+
+    // Unfiltered texels, for pixel art and lookup tables
+    pub fn nearest() -> SamplerDesc {
+        SamplerDesc::linear().filter(Filter::Nearest)
+    }
+
+    // Sets both the minification and magnification filter
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.min_filter = filter;
+        self.mag_filter = filter;
+        self
+    }
+
+    pub fn min_filter(mut self, filter: Filter) -> Self {
+        self.min_filter = filter;
+        self
+    }
+
+    pub fn mag_filter(mut self, filter: Filter) -> Self {
+        self.mag_filter = filter;
+        self
+    }
+
+    pub fn mipmap(mut self, mode: MipmapMode) -> Self {
+        self.mipmap = mode;
+        self
+    }
+
+    // Sets the wrap mode of every axis
+    pub fn wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap = [wrap; 3];
+        self
+    }
+
+    pub fn wrap_s(mut self, wrap: Wrap) -> Self {
+        self.wrap[0] = wrap;
+        self
+    }
+
+    pub fn wrap_t(mut self, wrap: Wrap) -> Self {
+        self.wrap[1] = wrap;
+        self
+    }
+
+    pub fn wrap_r(mut self, wrap: Wrap) -> Self {
+        self.wrap[2] = wrap;
+        self
+    }
+
+    pub fn border_color(mut self, color: [f32; 4]) -> Self {
+        self.border_color = color;
+        self
+    }
+
+    pub fn anisotropy(mut self, max_anisotropy: f32) -> Self {
+        self.max_anisotropy = max_anisotropy;
+        self
+    }
+
+    pub fn compare(mut self, func: CompareFunc) -> Self {
+        self.compare = Some(func);
+        self
+    }
+
+    // The GL_TEXTURE_MIN_FILTER value
+    pub fn min_filter_gl(&self) -> types::GLenum {
+        match (self.min_filter, self.mipmap) {
+            (Filter::Nearest, MipmapMode::None) => gl::NEAREST,
+            (Filter::Linear, MipmapMode::None) => gl::LINEAR,
+            (Filter::Nearest, MipmapMode::Nearest) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, MipmapMode::Nearest) => gl::LINEAR_MIPMAP_NEAREST,
+            (Filter::Nearest, MipmapMode::Linear) => gl::NEAREST_MIPMAP_LINEAR,
+            (Filter::Linear, MipmapMode::Linear) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    pub fn mag_filter_gl(&self) -> types::GLenum {
+        match self.mag_filter {
+            Filter::Nearest => gl::NEAREST,
+            Filter::Linear => gl::LINEAR,
+        }
+    }
+
+    // Writes every parameter through the given setters, which
+    // are either glTexParameter* or glSamplerParameter*
+    pub(crate) fn apply(
+        &self,
+        set_int: impl Fn(types::GLenum, types::GLint),
+        set_float: impl Fn(types::GLenum, types::GLfloat),
+        set_floats: impl Fn(types::GLenum, &[types::GLfloat; 4]),
+    ) {
+        set_int(gl::TEXTURE_MIN_FILTER, self.min_filter_gl() as types::GLint);
+        set_int(gl::TEXTURE_MAG_FILTER, self.mag_filter_gl() as types::GLint);
+        for (pname, wrap) in [gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R].into_iter().zip(self.wrap) {
+            set_int(pname, wrap as types::GLint);
+        }
+        set_floats(gl::TEXTURE_BORDER_COLOR, &self.border_color);
+        match self.compare {
+            Some(func) => {
+                set_int(gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as types::GLint);
+                set_int(gl::TEXTURE_COMPARE_FUNC, func as types::GLint);
+            }
+            None => set_int(gl::TEXTURE_COMPARE_MODE, gl::NONE as types::GLint),
+        }
+        if self.max_anisotropy > 1.0 {
+            let caps = GlCapabilities::current();
+            if caps.supports_anisotropic_filtering() {
+                set_float(TEXTURE_MAX_ANISOTROPY, self.max_anisotropy.min(caps.max_texture_max_anisotropy));
+            }
+        }
+    }
+
+    // Sets the parameters of the texture bound to `target`
+    pub(crate) fn apply_to_texture(&self, target: types::GLenum) {
+        self.apply(
+            |pname, param| unsafe { gl::TexParameteri(target, pname, param) },
+            |pname, param| unsafe { gl::TexParameterf(target, pname, param) },
+            |pname, params| unsafe { gl::TexParameterfv(target, pname, params.as_ptr()) },
+        );
+    }
+}
+
+// A GL sampler object; while bound to a texture unit its state
+// replaces the parameters of the texture bound there
+#[derive(Debug)]
+pub struct SamplerObject {
+    id: types::GLuint,
+    desc: SamplerDesc,
+}
+
+impl SamplerObject {
+    pub fn new(desc: &SamplerDesc) -> GfxResult<SamplerObject> {
+        if !GlCapabilities::current().supports_sampler_objects() {
+            return Err(GfxError::Unsupported("sampler objects"));
+        }
+        let mut id = 0;
+        unsafe { gl::GenSamplers(1, &mut id) };
+        if id == 0 {
+            return Err(GfxError::ObjectCreationError("Sampler"));
+        }
+        let mut sampler = SamplerObject { id, desc: *desc };
+        sampler.set_desc(desc);
+        Ok(sampler)
+    }
+
+    pub fn id(&self) -> types::GLuint {
+        self.id
+    }
+
+    pub fn desc(&self) -> &SamplerDesc {
+        &self.desc
+    }
+
+    pub fn set_desc(&mut self, desc: &SamplerDesc) {
+        self.desc = *desc;
+        let id = self.id;
+        desc.apply(
+            |pname, param| unsafe { gl::SamplerParameteri(id, pname, param) },
+            |pname, param| unsafe { gl::SamplerParameterf(id, pname, param) },
+            |pname, params| unsafe { gl::SamplerParameterfv(id, pname, params.as_ptr()) },
+        );
+    }
+
+    pub fn bind(&self, unit: u32) {
+        unsafe { gl::BindSampler(unit, self.id) }
+    }
+
+    // Returns texture unit `unit` to the textures' own parameters
+    pub fn unbind(unit: u32) {
+        unsafe { gl::BindSampler(unit, 0) }
+    }
+
+    pub fn set_label(&self, label: &str) {
+        object_label(gl::SAMPLER, self.id, label);
+    }
+}
+
+impl Drop for SamplerObject {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteSamplers(1, &self.id);
+        }
+    }
+}
+
+// Binds `texture` to texture unit `unit` for `target`,
+// leaving the active unit unchanged
+pub(crate) fn bind_texture_to_unit(target: types::GLenum, texture: types::GLuint, unit: u32) {
+    unsafe {
+        if GlCapabilities::current().supports_direct_state_access() {
+            gl::BindTextureUnit(unit, texture);
+        } else {
+            let mut active = 0;
+            gl::GetIntegerv(gl::ACTIVE_TEXTURE, &mut active);
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(target, texture);
+            gl::ActiveTexture(active as types::GLenum);
+        }
+    }
+}
@@ -0,0 +1,322 @@
+//! Uniform buffer objects with std140 layout
+//!
+//! std140 pads and aligns block members differently from
+//! Rust, so structs are written field by field into a byte
+//! buffer instead of being uploaded as-is. Describe a struct
+//! with `impl_std140!` and share it between programs through
+//! a `UniformBuffer`:
+//!
+//! ```ignore
+//! // layout(std140) uniform Camera { mat4 view; vec3 position; float time; };
+//! #[derive(Clone, Copy)]
+//! struct Camera {
+//!     view: [[f32; 4]; 4],
+//!     position: [f32; 3],
+//!     time: f32,
+//! }
+//! impl_std140!(Camera { view, position, time });
+//!
+//! let camera = UniformBuffer::new(&camera)?;
+//! scene_program.bind_uniform_buffer("Camera", &camera, 0)?;
+//! sky_program.bind_uniform_buffer("Camera", &camera, 0)?;
+//! ```
+use crate::debug::object_label;
+use crate::{types, Buffer, BufferType, GfxResult};
+use std::marker::PhantomData;
+
+// A value that can be written into a std140 uniform block.
+// Implemented for `f32`, `i32`, `u32` and `bool` scalars,
+// vectors (`[T; 2..=4]`), column-major matrices and arrays of
+// vectors (`[[T; 2..=4]; N]`, which share their std140 layout),
+// `Std140Array` and structs described with `impl_std140!`
+pub trait Std140 {
+    // Base alignment in bytes
+    const ALIGN: usize;
+
+    fn write_std140(&self, writer: &mut Std140Writer);
+}
+
+// Collects the bytes of a std140 block, inserting padding
+// so that every value starts at its base alignment
+#[derive(Debug, Clone, Default)]
+pub struct Std140Writer {
+    bytes: Vec<u8>,
+}
+
+impl Std140Writer {
+    pub fn new() -> Std140Writer {
+        Std140Writer::default()
+    }
+
+    // Aligns to `T::ALIGN` and writes `value`
+    pub fn write<T: Std140>(&mut self, value: &T) {
+        self.align(T::ALIGN);
+        value.write_std140(self);
+    }
+
+    // Pads with zeros up to a multiple of `align`
+    pub fn align(&mut self, align: usize) {
+        let len = self.bytes.len();
+        self.bytes.resize(len + (align - len % align) % align, 0);
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+// Lays out `value` as a whole uniform block
+pub fn to_std140<T: Std140>(value: &T) -> Vec<u8> {
+    let mut writer = Std140Writer::new();
+    writer.write(value);
+    // Blocks are padded like structs
+    writer.align(16);
+    writer.into_bytes()
+}
+
+// An array whose elements are padded to 16 bytes, for arrays
+// of scalars and structs; arrays of vectors can be plain
+// `[[f32; 4]; N]`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Std140Array<T, const N: usize>(pub [T; N]);
+
+impl<T: Std140, const N: usize> Std140 for Std140Array<T, N> {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        for element in &self.0 {
+            writer.write(element);
+            writer.align(16);
+        }
+    }
+}
+
+macro_rules! impl_std140_scalar {
+    ($($ty:ty),*) => {
+        $(
+            impl Std140 for $ty {
+                const ALIGN: usize = 4;
+
+                fn write_std140(&self, writer: &mut Std140Writer) {
+                    writer.write_bytes(&self.to_ne_bytes());
+                }
+            }
+
+            impl Std140 for [$ty; 2] {
+                const ALIGN: usize = 8;
+
+                fn write_std140(&self, writer: &mut Std140Writer) {
+                    for component in self {
+                        writer.write_bytes(&component.to_ne_bytes());
+                    }
+                }
+            }
+
+            // vec3 is aligned like a vec4 but only takes 12
+            // bytes, a following scalar fills the gap
+            impl Std140 for [$ty; 3] {
+                const ALIGN: usize = 16;
+
+                fn write_std140(&self, writer: &mut Std140Writer) {
+                    for component in self {
+                        writer.write_bytes(&component.to_ne_bytes());
+                    }
+                }
+            }
+
+            impl Std140 for [$ty; 4] {
+                const ALIGN: usize = 16;
+
+                fn write_std140(&self, writer: &mut Std140Writer) {
+                    for component in self {
+                        writer.write_bytes(&component.to_ne_bytes());
+                    }
+                }
+            }
+
+            // Matrix columns and array elements both take
+            // a full vec4 slot each
+            impl<const N: usize> Std140 for [[$ty; 2]; N] {
+                const ALIGN: usize = 16;
+
+                fn write_std140(&self, writer: &mut Std140Writer) {
+                    for column in self {
+                        writer.write(column);
+                        writer.align(16);
+                    }
+                }
+            }
+
+            impl<const N: usize> Std140 for [[$ty; 3]; N] {
+                const ALIGN: usize = 16;
+
+                fn write_std140(&self, writer: &mut Std140Writer) {
+                    for column in self {
+                        writer.write(column);
+                        writer.align(16);
+                    }
+                }
+            }
+
+            impl<const N: usize> Std140 for [[$ty; 4]; N] {
+                const ALIGN: usize = 16;
+
+                fn write_std140(&self, writer: &mut Std140Writer) {
+                    for column in self {
+                        writer.write(column);
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_std140_scalar!(f32, i32, u32);
+
+// GLSL bools take 4 bytes in a block
+impl Std140 for bool {
+    const ALIGN: usize = 4;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write_bytes(&(*self as u32).to_ne_bytes());
+    }
+}
+
+// Implements `Std140` for a struct; fields are written in the
+// order listed, which must match the member order of the GLSL
+// block or struct. Structs can be nested.
+#[macro_export]
+macro_rules! impl_std140 {
+    ($block:ty { $($field:ident),+ $(,)? }) => {
+        impl $crate::Std140 for $block {
+            // Structs are aligned like a vec4
+            const ALIGN: usize = 16;
+
+            fn write_std140(&self, writer: &mut $crate::Std140Writer) {
+                $(writer.write(&self.$field);)+
+                writer.align(16);
+            }
+        }
+    };
+}
+
+// A uniform buffer holding one `T` in std140 layout; bind it
+// to a binding point shared by every program using the block
+#[derive(Debug)]
+pub struct UniformBuffer<T: Std140> {
+    buffer: Buffer<u8>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Std140> UniformBuffer<T> {
+    pub fn new(value: &T) -> GfxResult<UniformBuffer<T>> {
+        let mut buffer = Buffer::new()?;
+        buffer.data(BufferType::Uniform, &to_std140(value), gl::DYNAMIC_DRAW);
+        buffer.unbind(BufferType::Uniform);
+        Ok(UniformBuffer { buffer, _marker: PhantomData })
+    }
+
+    // Uploads a new value, visible to every program the
+    // buffer is bound for
+    pub fn set(&self, value: &T) -> GfxResult<()> {
+        self.buffer.update(0, &to_std140(value))
+    }
+
+    pub fn buffer(&self) -> &Buffer<u8> {
+        &self.buffer
+    }
+
+    pub fn id(&self) -> types::GLuint {
+        self.buffer.id()
+    }
+
+    // Size of the std140 data; it must be at least the block
+    // size the program reports, and may be larger when the
+    // driver leaves out the block's trailing padding
+    pub fn size_bytes(&self) -> usize {
+        self.buffer.size_bytes()
+    }
+
+    // Binds the buffer to uniform binding point `binding`
+    pub fn bind_base(&self, binding: u32) {
+        unsafe {
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, self.buffer.id());
+        }
+    }
+
+    pub fn set_label(&self, label: &str) {
+        object_label(gl::BUFFER, self.buffer.id(), label);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn floats(bytes: &[u8]) -> Vec<f32> {
+        bytes.chunks(4).map(|chunk| f32::from_ne_bytes(chunk.try_into().unwrap())).collect()
+    }
+
+    #[derive(Clone, Copy)]
+    struct Light {
+        direction: [f32; 3],
+        intensity: f32,
+    }
+    crate::impl_std140!(Light { direction, intensity });
+
+    #[derive(Clone, Copy)]
+    struct Inner {
+        value: f32,
+    }
+    crate::impl_std140!(Inner { value });
+
+    #[derive(Clone, Copy)]
+    struct Outer {
+        before: f32,
+        inner: Inner,
+        after: f32,
+    }
+    crate::impl_std140!(Outer { before, inner, after });
+
+    #[test]
+    fn packs_float_after_vec3() {
+        let bytes = to_std140(&Light { direction: [1.0, 2.0, 3.0], intensity: 4.0 });
+        assert_eq!(floats(&bytes), [1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn pads_mat3_columns_to_vec4() {
+        let bytes = to_std140(&[[1.0f32, 2.0, 3.0], [4.0, 5.0, 6.0], [7.0, 8.0, 9.0]]);
+        assert_eq!(floats(&bytes), [1.0, 2.0, 3.0, 0.0, 4.0, 5.0, 6.0, 0.0, 7.0, 8.0, 9.0, 0.0]);
+    }
+
+    #[test]
+    fn pads_vec2_array_elements_to_16_bytes() {
+        let bytes = to_std140(&[[1.0f32, 2.0], [3.0, 4.0]]);
+        assert_eq!(floats(&bytes), [1.0, 2.0, 0.0, 0.0, 3.0, 4.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn pads_float_array_elements_to_16_bytes() {
+        let bytes = to_std140(&Std140Array([1.0f32, 2.0]));
+        assert_eq!(floats(&bytes), [1.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn aligns_nested_structs_to_16_bytes() {
+        let bytes = to_std140(&Outer { before: 1.0, inner: Inner { value: 2.0 }, after: 3.0 });
+        assert_eq!(floats(&bytes), [1.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 3.0, 0.0, 0.0, 0.0]);
+    }
+}
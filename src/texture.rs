@@ -0,0 +1,742 @@
+//! Texture formats, storage allocation, uploads and readback
+//!
+//! Uploads take plain slices whose element type picks the GL
+//! pixel type, e.g. `&[u8]` for 8-bit channels or `&[f32]`
+//! for float textures. Rows are read with the current unpack
+//! alignment, 4 by default; set it to 1 for tightly packed
+//! rows whose size isn't a multiple of 4:
+//!
+//! ```ignore
+//! let texture = Texture2D::new()?;
+//! texture.allocate(TextureFormat::R8, 256, 256, 1)?;
+//! set_unpack_alignment(1)?;
+//! texture.update_region(10, 10, glyph_width, glyph_height, glyph_bitmap)?;
+//! let pixels = texture.read_back()?;
+//! ```
+//!
+//! Cubemaps, 2D arrays and 3D textures share the object,
+//! parameter, sampler and mipmap API of `Texture2D`.
+use crate::debug::object_label;
+use crate::sampler::{bind_texture_to_unit, SamplerDesc};
+use crate::{types, GfxError, GfxResult, GlCapabilities, PixelArray, Texture2D};
+
+// Sized internal formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureFormat {
+    R8 = gl::R8 as isize,
+    Rg8 = gl::RG8 as isize,
+    Rgb8 = gl::RGB8 as isize,
+    Rgba8 = gl::RGBA8 as isize,
+    // sRGB encoded color with linear alpha, converted to
+    // linear when sampled
+    Srgb8Alpha8 = gl::SRGB8_ALPHA8 as isize,
+    R16F = gl::R16F as isize,
+    R32F = gl::R32F as isize,
+    Rgba16F = gl::RGBA16F as isize,
+    Rgba32F = gl::RGBA32F as isize,
+    Depth16 = gl::DEPTH_COMPONENT16 as isize,
+    Depth24 = gl::DEPTH_COMPONENT24 as isize,
+    Depth32F = gl::DEPTH_COMPONENT32F as isize,
+    Depth24Stencil8 = gl::DEPTH24_STENCIL8 as isize,
+    Depth32FStencil8 = gl::DEPTH32F_STENCIL8 as isize,
+}
+
+impl TextureFormat {
+    pub fn from_gl(format: types::GLenum) -> Option<TextureFormat> {
+        use TextureFormat::*;
+        [R8, Rg8, Rgb8, Rgba8, Srgb8Alpha8, R16F, R32F, Rgba16F, Rgba32F, Depth16, Depth24, Depth32F, Depth24Stencil8, Depth32FStencil8]
+            .into_iter()
+            .find(|&candidate| candidate as types::GLenum == format)
+    }
+
+    // Number of values per texel in uploads and readbacks;
+    // depth-stencil texels are a single packed value
+    pub fn channels(&self) -> usize {
+        use TextureFormat::*;
+        match self {
+            R8 | R16F | R32F => 1,
+            Rg8 => 2,
+            Rgb8 => 3,
+            Rgba8 | Srgb8Alpha8 | Rgba16F | Rgba32F => 4,
+            Depth16 | Depth24 | Depth32F | Depth24Stencil8 | Depth32FStencil8 => 1,
+        }
+    }
+
+    pub fn is_depth(&self) -> bool {
+        use TextureFormat::*;
+        matches!(self, Depth16 | Depth24 | Depth32F | Depth24Stencil8 | Depth32FStencil8)
+    }
+
+    pub fn has_stencil(&self) -> bool {
+        matches!(self, TextureFormat::Depth24Stencil8 | TextureFormat::Depth32FStencil8)
+    }
+
+    // The `format` argument of pixel transfers
+    pub(crate) fn pixel_format(&self) -> types::GLenum {
+        if self.has_stencil() {
+            return gl::DEPTH_STENCIL;
+        }
+        if self.is_depth() {
+            return gl::DEPTH_COMPONENT;
+        }
+        match self.channels() {
+            1 => gl::RED,
+            2 => gl::RG,
+            3 => gl::RGB,
+            _ => gl::RGBA,
+        }
+    }
+
+    // A pixel type the format accepts, for allocations
+    // without data
+    pub(crate) fn default_pixel_type(&self) -> types::GLenum {
+        use TextureFormat::*;
+        match self {
+            R8 | Rg8 | Rgb8 | Rgba8 | Srgb8Alpha8 => gl::UNSIGNED_BYTE,
+            R16F | R32F | Rgba16F | Rgba32F | Depth32F => gl::FLOAT,
+            Depth16 => gl::UNSIGNED_SHORT,
+            Depth24 => gl::UNSIGNED_INT,
+            Depth24Stencil8 => gl::UNSIGNED_INT_24_8,
+            Depth32FStencil8 => gl::FLOAT_32_UNSIGNED_INT_24_8_REV,
+        }
+    }
+
+    // The pixel type for uploading `T`s; depth-stencil data
+    // must already be packed
+    pub(crate) fn pixel_type<T: PixelType>(&self) -> GfxResult<types::GLenum> {
+        match self {
+            TextureFormat::Depth24Stencil8 if T::GL_TYPE == gl::UNSIGNED_INT => Ok(gl::UNSIGNED_INT_24_8),
+            TextureFormat::Depth24Stencil8 | TextureFormat::Depth32FStencil8 => {
+                Err(GfxError::Unsupported("depth-stencil uploads other than packed 24/8 u32s"))
+            }
+            _ => Ok(T::GL_TYPE),
+        }
+    }
+}
+
+// Element types of pixel data slices
+pub trait PixelType: Copy {
+    const GL_TYPE: types::GLenum;
+}
+
+impl PixelType for u8 {
+    const GL_TYPE: types::GLenum = gl::UNSIGNED_BYTE;
+}
+
+impl PixelType for i8 {
+    const GL_TYPE: types::GLenum = gl::BYTE;
+}
+
+impl PixelType for u16 {
+    const GL_TYPE: types::GLenum = gl::UNSIGNED_SHORT;
+}
+
+impl PixelType for i16 {
+    const GL_TYPE: types::GLenum = gl::SHORT;
+}
+
+impl PixelType for u32 {
+    const GL_TYPE: types::GLenum = gl::UNSIGNED_INT;
+}
+
+impl PixelType for i32 {
+    const GL_TYPE: types::GLenum = gl::INT;
+}
+
+impl PixelType for f32 {
+    const GL_TYPE: types::GLenum = gl::FLOAT;
+}
+
+// Row alignment of uploads in bytes: 1, 2, 4 (the default)
+// or 8. This is global state and affects every later upload.
+pub fn set_unpack_alignment(alignment: u32) -> GfxResult<()> {
+    if !matches!(alignment, 1 | 2 | 4 | 8) {
+        return Err(GfxError::InvalidTextureData("unpack alignment must be 1, 2, 4 or 8"));
+    }
+    unsafe {
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, alignment as types::GLint);
+    }
+    Ok(())
+}
+
+// Number of levels in a full mipmap chain down to 1x1
+pub fn mip_level_count(width: i32, height: i32) -> i32 {
+    32 - (width.max(height).max(1) as u32).leading_zeros() as i32
+}
+
+// Checks that `data` covers `width` x `height` texels of
+// `channels` values each, with rows aligned for unpacking
+pub(crate) fn check_upload_size<T>(data: &[T], width: i32, height: i32, depth: i32, channels: usize) -> GfxResult<()> {
+    let mut alignment = 4;
+    unsafe {
+        gl::GetIntegerv(gl::UNPACK_ALIGNMENT, &mut alignment);
+    }
+    let element = std::mem::size_of::<T>();
+    let row = width.max(0) as usize * channels * element;
+    // Rows of values at least as large as the alignment are
+    // never padded
+    let stride = if element >= alignment as usize { row } else { row.div_ceil(alignment as usize) * alignment as usize };
+    let rows = height.max(0) as usize * depth.max(0) as usize;
+    let expected = if rows == 0 { 0 } else { stride * (rows - 1) + row };
+    let found = std::mem::size_of_val(data);
+    if found < expected {
+        return Err(GfxError::TextureSizeMismatch { expected, found });
+    }
+    Ok(())
+}
+
+// Runs `read` with tightly packed rows, restoring the pack
+// alignment afterwards
+pub(crate) fn with_tight_packing<R>(read: impl FnOnce() -> R) -> R {
+    let mut alignment = 4;
+    unsafe {
+        gl::GetIntegerv(gl::PACK_ALIGNMENT, &mut alignment);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+    }
+    let result = read();
+    unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, alignment);
+    }
+    result
+}
+
+impl Texture2D {
+    // Sets the texture's own sampling parameters
+    pub fn set_sampler(&self, desc: &SamplerDesc) {
+        self.bind();
+        desc.apply_to_texture(gl::TEXTURE_2D);
+    }
+
+    // Binds the texture to texture unit `unit`, for the
+    // sampler uniform set to `Sampler(unit)`
+    pub fn bind_to_unit(&self, unit: u32) {
+        bind_texture_to_unit(gl::TEXTURE_2D, self.id(), unit);
+    }
+
+    // Allocates immutable storage for `levels` mip levels; the
+    // size and format can't change afterwards, only the contents.
+    // Falls back to mutable storage without GL 4.2 or
+    // ARB_texture_storage.
+    pub fn allocate(&self, format: TextureFormat, width: i32, height: i32, levels: i32) -> GfxResult<()> {
+        if width <= 0 || height <= 0 || levels < 1 || levels > mip_level_count(width, height) {
+            return Err(GfxError::InvalidTextureData("size and level count don't make a valid mip chain"));
+        }
+        self.bind();
+        unsafe {
+            if GlCapabilities::current().supports_texture_storage() {
+                gl::TexStorage2D(gl::TEXTURE_2D, levels, format as types::GLenum, width, height);
+            } else {
+                for level in 0..levels {
+                    gl::TexImage2D(
+                        gl::TEXTURE_2D,
+                        level,
+                        format as types::GLint,
+                        (width >> level).max(1),
+                        (height >> level).max(1),
+                        0,
+                        format.pixel_format(),
+                        format.default_pixel_type(),
+                        std::ptr::null(),
+                    );
+                }
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, levels - 1);
+            }
+        }
+        Ok(())
+    }
+
+    // (Re)creates level 0 with `format` and uploads `data`,
+    // `format.channels()` values per texel and rows starting at
+    // the bottom. Fails after allocate(), use update_region().
+    pub fn set_image_data<T: PixelType>(&self, format: TextureFormat, width: i32, height: i32, data: &[T]) -> GfxResult<()> {
+        check_upload_size(data, width, height, 1, format.channels())?;
+        let pixel_type = format.pixel_type::<T>()?;
+        self.bind();
+        check_mutable(gl::TEXTURE_2D)?;
+        unsafe {
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                format as types::GLint,
+                width,
+                height,
+                0,
+                format.pixel_format(),
+                pixel_type,
+                data.as_ptr().cast(),
+            );
+        }
+        Ok(())
+    }
+
+    // Replaces a `width` x `height` block of level 0 whose
+    // bottom left corner is at (`x`, `y`)
+    pub fn update_region<T: PixelType>(&self, x: i32, y: i32, width: i32, height: i32, data: &[T]) -> GfxResult<()> {
+        self.bind();
+        let format = self.format().ok_or(GfxError::InvalidTextureData("texture has no storage or an unknown format"))?;
+        let (texture_width, texture_height) = self.size();
+        if x < 0 || y < 0 || width < 0 || height < 0 || x + width > texture_width || y + height > texture_height {
+            return Err(GfxError::InvalidTextureData("region is outside of the texture"));
+        }
+        check_upload_size(data, width, height, 1, format.channels())?;
+        let pixel_type = format.pixel_type::<T>()?;
+        unsafe {
+            gl::TexSubImage2D(gl::TEXTURE_2D, 0, x, y, width, height, format.pixel_format(), pixel_type, data.as_ptr().cast());
+        }
+        Ok(())
+    }
+
+    // Size of level 0, (0, 0) before any storage is allocated
+    pub fn size(&self) -> (i32, i32) {
+        let (mut width, mut height) = (0, 0);
+        self.bind();
+        unsafe {
+            gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_WIDTH, &mut width);
+            gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_HEIGHT, &mut height);
+        }
+        (width, height)
+    }
+
+    // Internal format of level 0, if it is one of `TextureFormat`
+    pub fn format(&self) -> Option<TextureFormat> {
+        let mut format = 0;
+        self.bind();
+        unsafe {
+            gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_INTERNAL_FORMAT, &mut format);
+        }
+        TextureFormat::from_gl(format as types::GLenum)
+    }
+
+    // Downloads level 0 as 8-bit RGBA, missing channels read
+    // as 0 and alpha as 255; rows start at the bottom
+    pub fn read_back(&self) -> GfxResult<PixelArray> {
+        // Core on desktop GL, missing from every GLES version
+        if GlCapabilities::current().is_gles {
+            return Err(GfxError::Unsupported("texture readback"));
+        }
+        if self.format().is_some_and(|format| format.is_depth()) {
+            return Err(GfxError::InvalidTextureData("depth textures can only be read back as floats"));
+        }
+        let (width, height) = self.size();
+        let mut data = vec![0_u8; width as usize * height as usize * 4];
+        with_tight_packing(|| unsafe {
+            gl::GetTexImage(gl::TEXTURE_2D, 0, gl::RGBA, gl::UNSIGNED_BYTE, data.as_mut_ptr().cast());
+        });
+        PixelArray::from_bytearray(data, width as usize, height as usize)
+            .ok_or(GfxError::InvalidTextureData("readback returned no data"))
+    }
+
+    // Downloads level 0 as floats, `format().channels()` per
+    // texel; depth-stencil textures return only their depth
+    pub fn read_back_f32(&self) -> GfxResult<Vec<f32>> {
+        // Core on desktop GL, missing from every GLES version
+        if GlCapabilities::current().is_gles {
+            return Err(GfxError::Unsupported("texture readback"));
+        }
+        let format = self.format().ok_or(GfxError::InvalidTextureData("texture has no storage or an unknown format"))?;
+        let pixel_format = if format.is_depth() { gl::DEPTH_COMPONENT } else { format.pixel_format() };
+        let (width, height) = self.size();
+        let mut data = vec![0_f32; width as usize * height as usize * format.channels()];
+        with_tight_packing(|| unsafe {
+            gl::GetTexImage(gl::TEXTURE_2D, 0, pixel_format, gl::FLOAT, data.as_mut_ptr().cast());
+        });
+        Ok(data)
+    }
+}
+
+// Object management shared by the texture types besides
+// `Texture2D`; `$target` is what they bind to
+macro_rules! texture_object {
+    ($name:ident, $target:expr) => {
+        #[derive(Debug)]
+        pub struct $name(types::GLuint);
+
+        impl $name {
+            pub fn new() -> GfxResult<$name> {
+                let mut texture = 0;
+                unsafe { gl::GenTextures(1, &mut texture) };
+                if texture != 0 {
+                    Ok($name(texture))
+                } else {
+                    Err(GfxError::ObjectCreationError(stringify!($name)))
+                }
+            }
+
+            pub fn id(&self) -> types::GLuint {
+                self.0
+            }
+
+            pub fn bind(&self) {
+                unsafe { gl::BindTexture($target, self.0) }
+            }
+
+            pub fn unbind(&self) {
+                unsafe { gl::BindTexture($target, 0) }
+            }
+
+            // Names the object in debug output and graphics debuggers,
+            // the object must have been bound at least once
+            pub fn set_label(&self, label: &str) {
+                object_label(gl::TEXTURE, self.0, label);
+            }
+
+            // Sets a parameter of the bound texture
+            pub fn parameter(&self, pname: types::GLenum, param: types::GLint) {
+                unsafe {
+                    gl::TexParameteri($target, pname, param);
+                }
+            }
+
+            pub fn generate_mipmap(&self) {
+                unsafe {
+                    gl::GenerateMipmap($target);
+                }
+            }
+
+            // Sets the texture's own sampling parameters
+            pub fn set_sampler(&self, desc: &SamplerDesc) {
+                self.bind();
+                desc.apply_to_texture($target);
+            }
+
+            // Binds the texture to texture unit `unit`, for the
+            // sampler uniform set to `Sampler(unit)`
+            pub fn bind_to_unit(&self, unit: u32) {
+                bind_texture_to_unit($target, self.0, unit);
+            }
+        }
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                unsafe {
+                    gl::DeleteTextures(1, &self.0);
+                }
+            }
+        }
+    };
+}
+
+texture_object!(TextureCube, gl::TEXTURE_CUBE_MAP);
+texture_object!(Texture2DArray, gl::TEXTURE_2D_ARRAY);
+texture_object!(Texture3D, gl::TEXTURE_3D);
+
+// Size of level 0 of the texture bound to `target`
+fn level_size(target: types::GLenum) -> (i32, i32, i32) {
+    let (mut width, mut height, mut depth) = (0, 0, 0);
+    unsafe {
+        gl::GetTexLevelParameteriv(target, 0, gl::TEXTURE_WIDTH, &mut width);
+        gl::GetTexLevelParameteriv(target, 0, gl::TEXTURE_HEIGHT, &mut height);
+        gl::GetTexLevelParameteriv(target, 0, gl::TEXTURE_DEPTH, &mut depth);
+    }
+    (width, height, depth)
+}
+
+fn level_format(target: types::GLenum) -> GfxResult<TextureFormat> {
+    let mut format = 0;
+    unsafe {
+        gl::GetTexLevelParameteriv(target, 0, gl::TEXTURE_INTERNAL_FORMAT, &mut format);
+    }
+    TextureFormat::from_gl(format as types::GLenum).ok_or(GfxError::InvalidTextureData("texture has no storage or an unknown format"))
+}
+
+// Storage from allocate() can't be respecified, only updated
+fn check_mutable(target: types::GLenum) -> GfxResult<()> {
+    // The query itself is an error without texture storage
+    if !GlCapabilities::current().supports_texture_storage() {
+        return Ok(());
+    }
+    let mut immutable = 0;
+    unsafe {
+        gl::GetTexParameteriv(target, gl::TEXTURE_IMMUTABLE_FORMAT, &mut immutable);
+    }
+    if immutable != 0 {
+        Err(GfxError::InvalidTextureData("storage from allocate() can't be respecified, update a region instead"))
+    } else {
+        Ok(())
+    }
+}
+
+fn check_region(offset: [i32; 3], size: [i32; 3], texture_size: [i32; 3]) -> GfxResult<()> {
+    for axis in 0..3 {
+        if offset[axis] < 0 || size[axis] < 0 || offset[axis] + size[axis] > texture_size[axis] {
+            return Err(GfxError::InvalidTextureData("region is outside of the texture"));
+        }
+    }
+    Ok(())
+}
+
+// Allocates immutable storage for the bound 2D array or 3D
+// texture, or mutable storage without GL 4.2 or
+// ARB_texture_storage. Array layers keep their count at every
+// level, 3D depth is halved.
+fn allocate_3d(target: types::GLenum, format: TextureFormat, size: [i32; 3], levels: i32) -> GfxResult<()> {
+    let [width, height, depth] = size;
+    let max_levels = if target == gl::TEXTURE_3D { mip_level_count(width.max(depth), height) } else { mip_level_count(width, height) };
+    if width <= 0 || height <= 0 || depth <= 0 || levels < 1 || levels > max_levels {
+        return Err(GfxError::InvalidTextureData("size and level count don't make a valid mip chain"));
+    }
+    unsafe {
+        if GlCapabilities::current().supports_texture_storage() {
+            gl::TexStorage3D(target, levels, format as types::GLenum, width, height, depth);
+        } else {
+            for level in 0..levels {
+                let level_depth = if target == gl::TEXTURE_3D { (depth >> level).max(1) } else { depth };
+                gl::TexImage3D(
+                    target,
+                    level,
+                    format as types::GLint,
+                    (width >> level).max(1),
+                    (height >> level).max(1),
+                    level_depth,
+                    0,
+                    format.pixel_format(),
+                    format.default_pixel_type(),
+                    std::ptr::null(),
+                );
+            }
+            gl::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, levels - 1);
+        }
+    }
+    Ok(())
+}
+
+fn set_data_3d<T: PixelType>(target: types::GLenum, format: TextureFormat, size: [i32; 3], data: &[T]) -> GfxResult<()> {
+    let [width, height, depth] = size;
+    check_upload_size(data, width, height, depth, format.channels())?;
+    let pixel_type = format.pixel_type::<T>()?;
+    check_mutable(target)?;
+    unsafe {
+        gl::TexImage3D(target, 0, format as types::GLint, width, height, depth, 0, format.pixel_format(), pixel_type, data.as_ptr().cast());
+    }
+    Ok(())
+}
+
+fn update_region_3d<T: PixelType>(target: types::GLenum, offset: [i32; 3], size: [i32; 3], data: &[T]) -> GfxResult<()> {
+    let format = level_format(target)?;
+    let (width, height, depth) = level_size(target);
+    check_region(offset, size, [width, height, depth])?;
+    check_upload_size(data, size[0], size[1], size[2], format.channels())?;
+    let pixel_type = format.pixel_type::<T>()?;
+    let [x, y, z] = offset;
+    unsafe {
+        gl::TexSubImage3D(target, 0, x, y, z, size[0], size[1], size[2], format.pixel_format(), pixel_type, data.as_ptr().cast());
+    }
+    Ok(())
+}
+
+// Faces in the order GL numbers them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CubeFace {
+    PositiveX = gl::TEXTURE_CUBE_MAP_POSITIVE_X as isize,
+    NegativeX = gl::TEXTURE_CUBE_MAP_NEGATIVE_X as isize,
+    PositiveY = gl::TEXTURE_CUBE_MAP_POSITIVE_Y as isize,
+    NegativeY = gl::TEXTURE_CUBE_MAP_NEGATIVE_Y as isize,
+    PositiveZ = gl::TEXTURE_CUBE_MAP_POSITIVE_Z as isize,
+    NegativeZ = gl::TEXTURE_CUBE_MAP_NEGATIVE_Z as isize,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+
+    // Direction through the texel at `s`, `t` (both in -1..1,
+    // `t` increasing with the row) as the GL spec defines it
+    fn direction(&self, s: f32, t: f32) -> [f32; 3] {
+        match self {
+            CubeFace::PositiveX => [1.0, -t, -s],
+            CubeFace::NegativeX => [-1.0, -t, s],
+            CubeFace::PositiveY => [s, 1.0, t],
+            CubeFace::NegativeY => [s, -1.0, -t],
+            CubeFace::PositiveZ => [s, -t, 1.0],
+            CubeFace::NegativeZ => [-s, -t, -1.0],
+        }
+    }
+}
+
+impl TextureCube {
+    // Creates an RGBA8 cubemap from six square faces of the same
+    // size, in the order of `CubeFace::ALL`
+    pub fn from_faces(faces: [&PixelArray; 6]) -> GfxResult<TextureCube> {
+        let size = faces[0].width;
+        if faces.iter().any(|face| face.width != size || face.height != size) {
+            return Err(GfxError::InvalidTextureData("cubemap faces must be square and the same size"));
+        }
+        let texture = TextureCube::new()?;
+        texture.allocate(TextureFormat::Rgba8, size as i32, 1)?;
+        for (face, image) in CubeFace::ALL.into_iter().zip(faces) {
+            texture.update_face_region(face, 0, 0, size as i32, size as i32, &image.data())?;
+        }
+        Ok(texture)
+    }
+
+    // Resamples an equirectangular (latitude/longitude) image,
+    // like a panorama or a sky map, into faces of `face_size`
+    // texels. The image center looks down -Z, the top row is +Y.
+    pub fn from_equirectangular(image: &PixelArray, face_size: usize) -> GfxResult<TextureCube> {
+        if image.width == 0 || image.height == 0 || face_size == 0 {
+            return Err(GfxError::InvalidTextureData("empty image or face size"));
+        }
+        let texture = TextureCube::new()?;
+        texture.allocate(TextureFormat::Rgba8, face_size as i32, 1)?;
+        let mut data = Vec::with_capacity(face_size * face_size * 4);
+        for face in CubeFace::ALL {
+            data.clear();
+            for row in 0..face_size {
+                for column in 0..face_size {
+                    let s = 2.0 * (column as f32 + 0.5) / face_size as f32 - 1.0;
+                    let t = 2.0 * (row as f32 + 0.5) / face_size as f32 - 1.0;
+                    data.extend_from_slice(&sample_equirectangular(image, face.direction(s, t)));
+                }
+            }
+            texture.update_face_region(face, 0, 0, face_size as i32, face_size as i32, &data)?;
+        }
+        Ok(texture)
+    }
+
+    // Allocates immutable storage for six `size` x `size` faces
+    pub fn allocate(&self, format: TextureFormat, size: i32, levels: i32) -> GfxResult<()> {
+        if size <= 0 || levels < 1 || levels > mip_level_count(size, size) {
+            return Err(GfxError::InvalidTextureData("size and level count don't make a valid mip chain"));
+        }
+        self.bind();
+        unsafe {
+            if GlCapabilities::current().supports_texture_storage() {
+                gl::TexStorage2D(gl::TEXTURE_CUBE_MAP, levels, format as types::GLenum, size, size);
+            } else {
+                for face in CubeFace::ALL {
+                    for level in 0..levels {
+                        let level_size = (size >> level).max(1);
+                        gl::TexImage2D(
+                            face as types::GLenum,
+                            level,
+                            format as types::GLint,
+                            level_size,
+                            level_size,
+                            0,
+                            format.pixel_format(),
+                            format.default_pixel_type(),
+                            std::ptr::null(),
+                        );
+                    }
+                }
+                gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAX_LEVEL, levels - 1);
+            }
+        }
+        Ok(())
+    }
+
+    // Replaces a block of level 0 of `face`
+    pub fn update_face_region<T: PixelType>(&self, face: CubeFace, x: i32, y: i32, width: i32, height: i32, data: &[T]) -> GfxResult<()> {
+        self.bind();
+        let format = level_format(face as types::GLenum)?;
+        let (face_width, face_height, _) = level_size(face as types::GLenum);
+        check_region([x, y, 0], [width, height, 1], [face_width, face_height, 1])?;
+        check_upload_size(data, width, height, 1, format.channels())?;
+        let pixel_type = format.pixel_type::<T>()?;
+        unsafe {
+            gl::TexSubImage2D(face as types::GLenum, 0, x, y, width, height, format.pixel_format(), pixel_type, data.as_ptr().cast());
+        }
+        Ok(())
+    }
+
+    // Width and height of each face
+    pub fn size(&self) -> i32 {
+        self.bind();
+        level_size(CubeFace::PositiveX as types::GLenum).0
+    }
+}
+
+// Bilinear sample of an equirectangular image in `direction`
+fn sample_equirectangular(image: &PixelArray, direction: [f32; 3]) -> [u8; 4] {
+    use std::f32::consts::PI;
+    let [x, y, z] = direction;
+    let length = (x * x + y * y + z * z).sqrt();
+    let u = 0.5 + x.atan2(-z) / (2.0 * PI);
+    let v = (y / length).clamp(-1.0, 1.0).acos() / PI;
+
+    let fx = u * image.width as f32 - 0.5;
+    let fy = (v * image.height as f32 - 0.5).clamp(0.0, (image.height - 1) as f32);
+    let (x0, y0) = (fx.floor(), fy.floor());
+    let (wx, wy) = (fx - x0, fy - y0);
+    // Wraps around horizontally, clamps at the poles
+    let column = |offset: f32| (x0 + offset).rem_euclid(image.width as f32) as usize;
+    let row = |offset: f32| ((y0 + offset) as usize).min(image.height - 1);
+    let texel = |r: usize, c: usize| {
+        let p = image[[r, c]];
+        [p.r as f32, p.g as f32, p.b as f32, p.a as f32]
+    };
+    let (top_left, top_right) = (texel(row(0.0), column(0.0)), texel(row(0.0), column(1.0)));
+    let (bottom_left, bottom_right) = (texel(row(1.0), column(0.0)), texel(row(1.0), column(1.0)));
+    let mut out = [0; 4];
+    for channel in 0..4 {
+        let top = top_left[channel] * (1.0 - wx) + top_right[channel] * wx;
+        let bottom = bottom_left[channel] * (1.0 - wx) + bottom_right[channel] * wx;
+        out[channel] = (top * (1.0 - wy) + bottom * wy).round() as u8;
+    }
+    out
+}
+
+impl Texture2DArray {
+    // Allocates immutable storage for `layers` images of
+    // `width` x `height`
+    pub fn allocate(&self, format: TextureFormat, width: i32, height: i32, layers: i32, levels: i32) -> GfxResult<()> {
+        self.bind();
+        allocate_3d(gl::TEXTURE_2D_ARRAY, format, [width, height, layers], levels)
+    }
+
+    // (Re)creates level 0 with all layers, stored one after
+    // the other in `data`
+    pub fn set_data<T: PixelType>(&self, format: TextureFormat, width: i32, height: i32, layers: i32, data: &[T]) -> GfxResult<()> {
+        self.bind();
+        set_data_3d(gl::TEXTURE_2D_ARRAY, format, [width, height, layers], data)
+    }
+
+    // Replaces a block in `layer_count` layers starting at `layer`
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_region<T: PixelType>(&self, x: i32, y: i32, layer: i32, width: i32, height: i32, layer_count: i32, data: &[T]) -> GfxResult<()> {
+        self.bind();
+        update_region_3d(gl::TEXTURE_2D_ARRAY, [x, y, layer], [width, height, layer_count], data)
+    }
+
+    // Replaces a whole layer with an RGBA image
+    pub fn set_layer(&self, layer: i32, image: &PixelArray) -> GfxResult<()> {
+        self.update_region(0, 0, layer, image.width as i32, image.height as i32, 1, &image.data())
+    }
+
+    // Width, height and number of layers
+    pub fn size(&self) -> (i32, i32, i32) {
+        self.bind();
+        level_size(gl::TEXTURE_2D_ARRAY)
+    }
+}
+
+impl Texture3D {
+    pub fn allocate(&self, format: TextureFormat, width: i32, height: i32, depth: i32, levels: i32) -> GfxResult<()> {
+        self.bind();
+        allocate_3d(gl::TEXTURE_3D, format, [width, height, depth], levels)
+    }
+
+    // (Re)creates level 0 from slices stored one after the
+    // other in `data`, starting at z = 0
+    pub fn set_data<T: PixelType>(&self, format: TextureFormat, width: i32, height: i32, depth: i32, data: &[T]) -> GfxResult<()> {
+        self.bind();
+        set_data_3d(gl::TEXTURE_3D, format, [width, height, depth], data)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_region<T: PixelType>(&self, x: i32, y: i32, z: i32, width: i32, height: i32, depth: i32, data: &[T]) -> GfxResult<()> {
+        self.bind();
+        update_region_3d(gl::TEXTURE_3D, [x, y, z], [width, height, depth], data)
+    }
+
+    pub fn size(&self) -> (i32, i32, i32) {
+        self.bind();
+        level_size(gl::TEXTURE_3D)
+    }
+}
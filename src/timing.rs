@@ -0,0 +1,89 @@
+//! Frame pacing and timing for the render loop
+use std::time::{Duration, Instant};
+
+// Controls when `GLWindowHandler::run_loop` redraws the window
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RedrawMode {
+    // Only redraw when the OS asks for it (e.g. when the window
+    // is exposed) or when `GLWindow::request_redraw()` is called
+    #[default]
+    OnDemand,
+    // Redraw as fast as possible (or at the display refresh rate
+    // with vsync enabled)
+    Continuous,
+    // Redraw at a fixed number of frames per second
+    TargetFps(f64),
+}
+
+// Weight given to the newest frame when smoothing the FPS
+// counter, smaller values give a steadier but slower reading
+const FPS_SMOOTHING: f64 = 0.1;
+
+#[derive(Debug, Clone)]
+pub struct FrameTiming {
+    start_time: Instant,
+    last_frame: Instant,
+    delta: Duration,
+    frame_index: u64,
+    smoothed_frame_time: f64,
+}
+
+impl FrameTiming {
+    pub fn new() -> FrameTiming {
+        let now = Instant::now();
+        FrameTiming {
+            start_time: now,
+            last_frame: now,
+            delta: Duration::ZERO,
+            frame_index: 0,
+            smoothed_frame_time: 0.0,
+        }
+    }
+
+    // Advances to the next frame, returning the time since
+    // the previous frame in seconds
+    pub(crate) fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        self.delta = now.duration_since(self.last_frame);
+        self.last_frame = now;
+        let dt = self.delta.as_secs_f64();
+        // Seed the average with the first real frame time so the
+        // counter doesn't start from an absurdly high value
+        if self.frame_index == 0 {
+            self.smoothed_frame_time = dt;
+        } else {
+            self.smoothed_frame_time += FPS_SMOOTHING * (dt - self.smoothed_frame_time);
+        }
+        self.frame_index += 1;
+        dt as f32
+    }
+
+    // Time since the render loop started
+    pub fn elapsed(&self) -> Duration {
+        self.start_time.elapsed()
+    }
+
+    // Time between the previous frame and the current frame
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    // Number of frames rendered, the first frame has index 1
+    pub fn frame_index(&self) -> u64 {
+        self.frame_index
+    }
+
+    pub fn fps(&self) -> f64 {
+        if self.smoothed_frame_time > 0.0 {
+            1.0 / self.smoothed_frame_time
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Default for FrameTiming {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,365 @@
+//! Uniform values and program reflection
+use crate::types;
+use std::collections::HashMap;
+
+// An active uniform as reported by the driver; arrays are
+// stored under their base name, e.g. `lights` for `lights[0]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UniformInfo {
+    pub name: String,
+    // -1 for uniforms inside a uniform block
+    pub location: types::GLint,
+    pub gl_type: types::GLenum,
+    // Number of array elements, 1 for non-arrays
+    pub size: i32,
+}
+
+// An active uniform block; `size` is the data size the
+// driver expects the bound buffer to provide
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UniformBlockInfo {
+    pub name: String,
+    pub index: types::GLuint,
+    pub size: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeInfo {
+    pub name: String,
+    pub location: types::GLint,
+    pub gl_type: types::GLenum,
+    pub size: i32,
+}
+
+// Texture unit for a sampler or image uniform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(transparent)]
+pub struct Sampler(pub i32);
+
+// A Rust value that can be uploaded to a uniform; implemented
+// for scalars, vectors (`[T; 2..=4]`), column-major matrices
+// (`[[f32; N]; N]`), `bool`, `Sampler` and slices of all of
+// those for uniform arrays
+pub trait UniformValue {
+    // GLSL type name, used in error messages
+    fn glsl_type() -> &'static str;
+    fn matches(gl_type: types::GLenum) -> bool;
+    // Number of array elements this value covers
+    fn count(&self) -> usize {
+        1
+    }
+    // Uploads to the program currently in use
+    fn upload(&self, location: types::GLint);
+}
+
+macro_rules! impl_uniform_value {
+    ($($ty:ty, $glsl:expr, |$gl_type:ident| $matches:expr, |$loc:ident, $n:ident, $ptr:ident| $upload:expr;)*) => {
+        $(
+            impl UniformValue for $ty {
+                fn glsl_type() -> &'static str {
+                    $glsl
+                }
+
+                fn matches($gl_type: types::GLenum) -> bool {
+                    $matches
+                }
+
+                fn upload(&self, location: types::GLint) {
+                    std::slice::from_ref(self).upload(location)
+                }
+            }
+
+            impl UniformValue for &[$ty] {
+                fn glsl_type() -> &'static str {
+                    concat!($glsl, "[]")
+                }
+
+                fn matches(gl_type: types::GLenum) -> bool {
+                    <$ty as UniformValue>::matches(gl_type)
+                }
+
+                fn count(&self) -> usize {
+                    self.len()
+                }
+
+                fn upload(&self, location: types::GLint) {
+                    let ($loc, $n, $ptr) = (location, self.len() as types::GLsizei, self.as_ptr());
+                    unsafe { $upload }
+                }
+            }
+        )*
+    };
+}
+
+impl_uniform_value! {
+    f32, "float", |t| t == gl::FLOAT, |l, n, p| gl::Uniform1fv(l, n, p);
+    [f32; 2], "vec2", |t| t == gl::FLOAT_VEC2, |l, n, p| gl::Uniform2fv(l, n, p.cast());
+    [f32; 3], "vec3", |t| t == gl::FLOAT_VEC3, |l, n, p| gl::Uniform3fv(l, n, p.cast());
+    [f32; 4], "vec4", |t| t == gl::FLOAT_VEC4, |l, n, p| gl::Uniform4fv(l, n, p.cast());
+    // GL accepts integers for bools and for samplers
+    i32, "int", |t| t == gl::INT || t == gl::BOOL || is_opaque_type(t), |l, n, p| gl::Uniform1iv(l, n, p);
+    [i32; 2], "ivec2", |t| t == gl::INT_VEC2 || t == gl::BOOL_VEC2, |l, n, p| gl::Uniform2iv(l, n, p.cast());
+    [i32; 3], "ivec3", |t| t == gl::INT_VEC3 || t == gl::BOOL_VEC3, |l, n, p| gl::Uniform3iv(l, n, p.cast());
+    [i32; 4], "ivec4", |t| t == gl::INT_VEC4 || t == gl::BOOL_VEC4, |l, n, p| gl::Uniform4iv(l, n, p.cast());
+    u32, "uint", |t| t == gl::UNSIGNED_INT || t == gl::BOOL, |l, n, p| gl::Uniform1uiv(l, n, p);
+    [u32; 2], "uvec2", |t| t == gl::UNSIGNED_INT_VEC2 || t == gl::BOOL_VEC2, |l, n, p| gl::Uniform2uiv(l, n, p.cast());
+    [u32; 3], "uvec3", |t| t == gl::UNSIGNED_INT_VEC3 || t == gl::BOOL_VEC3, |l, n, p| gl::Uniform3uiv(l, n, p.cast());
+    [u32; 4], "uvec4", |t| t == gl::UNSIGNED_INT_VEC4 || t == gl::BOOL_VEC4, |l, n, p| gl::Uniform4uiv(l, n, p.cast());
+    [[f32; 2]; 2], "mat2", |t| t == gl::FLOAT_MAT2, |l, n, p| gl::UniformMatrix2fv(l, n, gl::FALSE, p.cast());
+    [[f32; 3]; 3], "mat3", |t| t == gl::FLOAT_MAT3, |l, n, p| gl::UniformMatrix3fv(l, n, gl::FALSE, p.cast());
+    [[f32; 4]; 4], "mat4", |t| t == gl::FLOAT_MAT4, |l, n, p| gl::UniformMatrix4fv(l, n, gl::FALSE, p.cast());
+    Sampler, "sampler", |t| is_opaque_type(t), |l, n, p| gl::Uniform1iv(l, n, p.cast());
+}
+
+impl UniformValue for bool {
+    fn glsl_type() -> &'static str {
+        "bool"
+    }
+
+    fn matches(gl_type: types::GLenum) -> bool {
+        gl_type == gl::BOOL
+    }
+
+    fn upload(&self, location: types::GLint) {
+        unsafe {
+            gl::Uniform1i(location, *self as types::GLint);
+        }
+    }
+}
+
+impl UniformValue for &[bool] {
+    fn glsl_type() -> &'static str {
+        "bool[]"
+    }
+
+    fn matches(gl_type: types::GLenum) -> bool {
+        gl_type == gl::BOOL
+    }
+
+    fn count(&self) -> usize {
+        self.len()
+    }
+
+    fn upload(&self, location: types::GLint) {
+        let values: Vec<types::GLint> = self.iter().map(|&b| b as types::GLint).collect();
+        unsafe {
+            gl::Uniform1iv(location, values.len() as types::GLsizei, values.as_ptr());
+        }
+    }
+}
+
+// Samplers and images, which are set to a texture or image unit
+pub(crate) fn is_opaque_type(gl_type: types::GLenum) -> bool {
+    matches!(
+        gl_type,
+        gl::SAMPLER_1D
+            | gl::SAMPLER_2D
+            | gl::SAMPLER_3D
+            | gl::SAMPLER_CUBE
+            | gl::SAMPLER_1D_SHADOW
+            | gl::SAMPLER_2D_SHADOW
+            | gl::SAMPLER_1D_ARRAY
+            | gl::SAMPLER_2D_ARRAY
+            | gl::SAMPLER_1D_ARRAY_SHADOW
+            | gl::SAMPLER_2D_ARRAY_SHADOW
+            | gl::SAMPLER_2D_MULTISAMPLE
+            | gl::SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::SAMPLER_CUBE_SHADOW
+            | gl::SAMPLER_CUBE_MAP_ARRAY
+            | gl::SAMPLER_CUBE_MAP_ARRAY_SHADOW
+            | gl::SAMPLER_BUFFER
+            | gl::SAMPLER_2D_RECT
+            | gl::SAMPLER_2D_RECT_SHADOW
+            | gl::INT_SAMPLER_1D
+            | gl::INT_SAMPLER_2D
+            | gl::INT_SAMPLER_3D
+            | gl::INT_SAMPLER_CUBE
+            | gl::INT_SAMPLER_1D_ARRAY
+            | gl::INT_SAMPLER_2D_ARRAY
+            | gl::INT_SAMPLER_2D_MULTISAMPLE
+            | gl::INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::INT_SAMPLER_CUBE_MAP_ARRAY
+            | gl::INT_SAMPLER_BUFFER
+            | gl::INT_SAMPLER_2D_RECT
+            | gl::UNSIGNED_INT_SAMPLER_1D
+            | gl::UNSIGNED_INT_SAMPLER_2D
+            | gl::UNSIGNED_INT_SAMPLER_3D
+            | gl::UNSIGNED_INT_SAMPLER_CUBE
+            | gl::UNSIGNED_INT_SAMPLER_1D_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE
+            | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_CUBE_MAP_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_BUFFER
+            | gl::UNSIGNED_INT_SAMPLER_2D_RECT
+            | gl::IMAGE_1D
+            | gl::IMAGE_2D
+            | gl::IMAGE_3D
+            | gl::IMAGE_CUBE
+            | gl::IMAGE_2D_ARRAY
+            | gl::IMAGE_BUFFER
+            | gl::INT_IMAGE_2D
+            | gl::INT_IMAGE_3D
+            | gl::INT_IMAGE_2D_ARRAY
+            | gl::UNSIGNED_INT_IMAGE_2D
+            | gl::UNSIGNED_INT_IMAGE_3D
+            | gl::UNSIGNED_INT_IMAGE_2D_ARRAY
+    )
+}
+
+// GLSL name of a uniform or attribute type
+pub fn glsl_type_name(gl_type: types::GLenum) -> &'static str {
+    match gl_type {
+        gl::FLOAT => "float",
+        gl::FLOAT_VEC2 => "vec2",
+        gl::FLOAT_VEC3 => "vec3",
+        gl::FLOAT_VEC4 => "vec4",
+        gl::DOUBLE => "double",
+        gl::DOUBLE_VEC2 => "dvec2",
+        gl::DOUBLE_VEC3 => "dvec3",
+        gl::DOUBLE_VEC4 => "dvec4",
+        gl::INT => "int",
+        gl::INT_VEC2 => "ivec2",
+        gl::INT_VEC3 => "ivec3",
+        gl::INT_VEC4 => "ivec4",
+        gl::UNSIGNED_INT => "uint",
+        gl::UNSIGNED_INT_VEC2 => "uvec2",
+        gl::UNSIGNED_INT_VEC3 => "uvec3",
+        gl::UNSIGNED_INT_VEC4 => "uvec4",
+        gl::BOOL => "bool",
+        gl::BOOL_VEC2 => "bvec2",
+        gl::BOOL_VEC3 => "bvec3",
+        gl::BOOL_VEC4 => "bvec4",
+        gl::FLOAT_MAT2 => "mat2",
+        gl::FLOAT_MAT3 => "mat3",
+        gl::FLOAT_MAT4 => "mat4",
+        gl::FLOAT_MAT2x3 => "mat2x3",
+        gl::FLOAT_MAT2x4 => "mat2x4",
+        gl::FLOAT_MAT3x2 => "mat3x2",
+        gl::FLOAT_MAT3x4 => "mat3x4",
+        gl::FLOAT_MAT4x2 => "mat4x2",
+        gl::FLOAT_MAT4x3 => "mat4x3",
+        gl::SAMPLER_2D => "sampler2D",
+        gl::SAMPLER_3D => "sampler3D",
+        gl::SAMPLER_CUBE => "samplerCube",
+        gl::SAMPLER_2D_ARRAY => "sampler2DArray",
+        gl::SAMPLER_2D_SHADOW => "sampler2DShadow",
+        gl::IMAGE_2D => "image2D",
+        t if is_opaque_type(t) => "sampler/image",
+        _ => "unknown",
+    }
+}
+
+// Copies the current value at `from_location` in program `from`
+// to `to_location` in the program in use; returns false for
+// types that can't be copied (doubles and non-square matrices)
+pub(crate) fn copy_uniform_value(from: types::GLuint, from_location: types::GLint, to_location: types::GLint, gl_type: types::GLenum) -> bool {
+    let (mut f, mut i, mut u) = ([0_f32; 16], [0_i32; 4], [0_u32; 4]);
+    unsafe {
+        match gl_type {
+            gl::FLOAT | gl::FLOAT_VEC2 | gl::FLOAT_VEC3 | gl::FLOAT_VEC4 | gl::FLOAT_MAT2 | gl::FLOAT_MAT3 | gl::FLOAT_MAT4 => {
+                gl::GetUniformfv(from, from_location, f.as_mut_ptr());
+            }
+            gl::UNSIGNED_INT | gl::UNSIGNED_INT_VEC2 | gl::UNSIGNED_INT_VEC3 | gl::UNSIGNED_INT_VEC4 => {
+                gl::GetUniformuiv(from, from_location, u.as_mut_ptr());
+            }
+            gl::INT | gl::INT_VEC2 | gl::INT_VEC3 | gl::INT_VEC4 | gl::BOOL | gl::BOOL_VEC2 | gl::BOOL_VEC3 | gl::BOOL_VEC4 => {
+                gl::GetUniformiv(from, from_location, i.as_mut_ptr());
+            }
+            t if is_opaque_type(t) => gl::GetUniformiv(from, from_location, i.as_mut_ptr()),
+            _ => return false,
+        }
+        let (l, fp, ip, up) = (to_location, f.as_ptr(), i.as_ptr(), u.as_ptr());
+        match gl_type {
+            gl::FLOAT => gl::Uniform1fv(l, 1, fp),
+            gl::FLOAT_VEC2 => gl::Uniform2fv(l, 1, fp),
+            gl::FLOAT_VEC3 => gl::Uniform3fv(l, 1, fp),
+            gl::FLOAT_VEC4 => gl::Uniform4fv(l, 1, fp),
+            gl::FLOAT_MAT2 => gl::UniformMatrix2fv(l, 1, gl::FALSE, fp),
+            gl::FLOAT_MAT3 => gl::UniformMatrix3fv(l, 1, gl::FALSE, fp),
+            gl::FLOAT_MAT4 => gl::UniformMatrix4fv(l, 1, gl::FALSE, fp),
+            gl::UNSIGNED_INT => gl::Uniform1uiv(l, 1, up),
+            gl::UNSIGNED_INT_VEC2 => gl::Uniform2uiv(l, 1, up),
+            gl::UNSIGNED_INT_VEC3 => gl::Uniform3uiv(l, 1, up),
+            gl::UNSIGNED_INT_VEC4 => gl::Uniform4uiv(l, 1, up),
+            gl::INT_VEC2 | gl::BOOL_VEC2 => gl::Uniform2iv(l, 1, ip),
+            gl::INT_VEC3 | gl::BOOL_VEC3 => gl::Uniform3iv(l, 1, ip),
+            gl::INT_VEC4 | gl::BOOL_VEC4 => gl::Uniform4iv(l, 1, ip),
+            // int, bool, samplers and images
+            _ => gl::Uniform1iv(l, 1, ip),
+        }
+    }
+    true
+}
+
+// Strips the `[0]` GL appends to the names of arrays
+fn base_name(name: &str) -> &str {
+    name.strip_suffix("[0]").unwrap_or(name)
+}
+
+fn max_name_length(program: types::GLuint, pname: types::GLenum) -> usize {
+    let mut length = 0;
+    unsafe {
+        gl::GetProgramiv(program, pname, &mut length);
+    }
+    length.max(1) as usize
+}
+
+pub(crate) fn reflect_uniforms(program: types::GLuint) -> HashMap<String, UniformInfo> {
+    let mut count = 0;
+    unsafe {
+        gl::GetProgramiv(program, gl::ACTIVE_UNIFORMS, &mut count);
+    }
+    let mut buf = vec![0_u8; max_name_length(program, gl::ACTIVE_UNIFORM_MAX_LENGTH)];
+    let mut uniforms = HashMap::new();
+    for i in 0..count.max(0) as types::GLuint {
+        let (mut length, mut size, mut gl_type) = (0, 0, 0);
+        unsafe {
+            gl::GetActiveUniform(program, i, buf.len() as types::GLsizei, &mut length, &mut size, &mut gl_type, buf.as_mut_ptr().cast());
+        }
+        let full_name = String::from_utf8_lossy(&buf[..length as usize]).into_owned();
+        let location = unsafe { gl::GetUniformLocation(program, buf.as_ptr().cast()) };
+        let name = base_name(&full_name).to_string();
+        uniforms.insert(name.clone(), UniformInfo { name, location, gl_type, size });
+    }
+    uniforms
+}
+
+pub(crate) fn reflect_uniform_blocks(program: types::GLuint) -> HashMap<String, UniformBlockInfo> {
+    let mut count = 0;
+    unsafe {
+        gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_BLOCKS, &mut count);
+    }
+    let mut buf = vec![0_u8; max_name_length(program, gl::ACTIVE_UNIFORM_BLOCK_MAX_NAME_LENGTH)];
+    let mut blocks = HashMap::new();
+    for index in 0..count.max(0) as types::GLuint {
+        let (mut length, mut size) = (0, 0);
+        unsafe {
+            gl::GetActiveUniformBlockName(program, index, buf.len() as types::GLsizei, &mut length, buf.as_mut_ptr().cast());
+            gl::GetActiveUniformBlockiv(program, index, gl::UNIFORM_BLOCK_DATA_SIZE, &mut size);
+        }
+        let name = String::from_utf8_lossy(&buf[..length as usize]).into_owned();
+        blocks.insert(name.clone(), UniformBlockInfo { name, index, size: size as usize });
+    }
+    blocks
+}
+
+pub(crate) fn reflect_attributes(program: types::GLuint) -> HashMap<String, AttributeInfo> {
+    let mut count = 0;
+    unsafe {
+        gl::GetProgramiv(program, gl::ACTIVE_ATTRIBUTES, &mut count);
+    }
+    let mut buf = vec![0_u8; max_name_length(program, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH)];
+    let mut attributes = HashMap::new();
+    for i in 0..count.max(0) as types::GLuint {
+        let (mut length, mut size, mut gl_type) = (0, 0, 0);
+        unsafe {
+            gl::GetActiveAttrib(program, i, buf.len() as types::GLsizei, &mut length, &mut size, &mut gl_type, buf.as_mut_ptr().cast());
+        }
+        let full_name = String::from_utf8_lossy(&buf[..length as usize]).into_owned();
+        let location = unsafe { gl::GetAttribLocation(program, buf.as_ptr().cast()) };
+        let name = base_name(&full_name).to_string();
+        attributes.insert(name.clone(), AttributeInfo { name, location, gl_type, size });
+    }
+    attributes
+}
@@ -0,0 +1,223 @@
+//! Typed vertex layouts for configuring vertex arrays
+//!
+//! Describe a `#[repr(C)]` vertex struct once with
+//! `impl_vertex_layout!` and let `VertexArray::with_layout()`
+//! look up and configure every attribute:
+//!
+//! ```ignore
+//! #[repr(C)]
+//! #[derive(Clone, Copy)]
+//! struct Vertex {
+//!     position: [f32; 2],
+//!     color: Normalized<[u8; 4]>,
+//!     material_id: u32,
+//! }
+//! impl_vertex_layout!(Vertex { position, color, material_id });
+//! let vao = VertexArray::with_layout::<Vertex>(&program, &vbo)?;
+//! ```
+use crate::types;
+
+// How the shader sees an attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttribKind {
+    // Converted to float without normalization (vec* inputs)
+    Float,
+    // Integers mapped to [0, 1] or [-1, 1] (vec* inputs)
+    Normalized,
+    // Passed through as integers (ivec*/uvec* inputs)
+    Integer,
+    // Passed through as doubles (dvec* inputs)
+    Double,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub name: &'static str,
+    // Components per column, 1 to 4
+    pub components: i32,
+    // Matrices take one attribute location per column, or
+    // two for the columns of dmat3 and dmat4
+    pub columns: u32,
+    pub gl_type: types::GLenum,
+    pub kind: AttribKind,
+    // Byte offset of the field inside the vertex struct
+    pub offset: usize,
+}
+
+impl VertexAttribute {
+    // Size in bytes of a single column
+    pub fn column_size(&self) -> usize {
+        self.components as usize * gl_type_size(self.gl_type)
+    }
+
+    // Attribute location of `column` when the attribute
+    // itself is at `location`; dvec3 and dvec4 columns take
+    // two locations each
+    pub fn column_location(&self, location: types::GLuint, column: u32) -> types::GLuint {
+        let step = if self.kind == AttribKind::Double && self.components > 2 { 2 } else { 1 };
+        location + column * step
+    }
+}
+
+pub trait VertexLayout: Sized {
+    fn attributes() -> Vec<VertexAttribute>;
+
+    fn stride() -> usize {
+        std::mem::size_of::<Self>()
+    }
+}
+
+// Implemented for the Rust types that can be used
+// as fields of a vertex struct
+pub trait VertexAttribFormat {
+    const COMPONENTS: i32;
+    const COLUMNS: u32 = 1;
+    const GL_TYPE: types::GLenum;
+    const KIND: AttribKind;
+}
+
+// Marks an integer field as normalized, so a shader
+// `vec4` input sees e.g. `[u8; 4]` colors as 0.0..1.0
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Normalized<T>(pub T);
+
+fn gl_type_size(gl_type: types::GLenum) -> usize {
+    match gl_type {
+        gl::BYTE | gl::UNSIGNED_BYTE => 1,
+        gl::SHORT | gl::UNSIGNED_SHORT | gl::HALF_FLOAT => 2,
+        gl::DOUBLE => 8,
+        _ => 4,
+    }
+}
+
+// Evaluated when a vertex struct's layout is built, so arrays
+// GL can't take as an attribute fail to compile instead of
+// failing in glVertexAttribPointer
+const fn components(n: usize) -> i32 {
+    assert!(n >= 1 && n <= 4, "vertex attributes have 1 to 4 components");
+    n as i32
+}
+
+const fn columns(m: usize) -> u32 {
+    assert!(m >= 1 && m <= 4, "matrix vertex attributes have 1 to 4 columns");
+    m as u32
+}
+
+macro_rules! impl_attrib_format {
+    ($($ty:ty => $gl_type:expr, $kind:ident;)*) => {
+        $(
+            impl VertexAttribFormat for $ty {
+                const COMPONENTS: i32 = 1;
+                const GL_TYPE: types::GLenum = $gl_type;
+                const KIND: AttribKind = AttribKind::$kind;
+            }
+
+            impl<const N: usize> VertexAttribFormat for [$ty; N] {
+                const COMPONENTS: i32 = components(N);
+                const GL_TYPE: types::GLenum = $gl_type;
+                const KIND: AttribKind = AttribKind::$kind;
+            }
+
+            impl<const N: usize, const M: usize> VertexAttribFormat for [[$ty; N]; M] {
+                const COMPONENTS: i32 = components(N);
+                const COLUMNS: u32 = columns(M);
+                const GL_TYPE: types::GLenum = $gl_type;
+                const KIND: AttribKind = AttribKind::$kind;
+            }
+        )*
+    };
+}
+
+impl_attrib_format! {
+    f32 => gl::FLOAT, Float;
+    f64 => gl::DOUBLE, Double;
+    i8 => gl::BYTE, Integer;
+    u8 => gl::UNSIGNED_BYTE, Integer;
+    i16 => gl::SHORT, Integer;
+    u16 => gl::UNSIGNED_SHORT, Integer;
+    i32 => gl::INT, Integer;
+    u32 => gl::UNSIGNED_INT, Integer;
+}
+
+impl<T: VertexAttribFormat> VertexAttribFormat for Normalized<T> {
+    const COMPONENTS: i32 = T::COMPONENTS;
+    const COLUMNS: u32 = T::COLUMNS;
+    const GL_TYPE: types::GLenum = T::GL_TYPE;
+    const KIND: AttribKind = AttribKind::Normalized;
+}
+
+// Used by `impl_vertex_layout!` to get the format of a
+// field from a pointer to it, since macro_rules can't
+// name the field's type
+#[doc(hidden)]
+pub fn attribute_for<T: VertexAttribFormat>(_field: *const T, name: &'static str, offset: usize) -> VertexAttribute {
+    VertexAttribute {
+        name,
+        components: T::COMPONENTS,
+        columns: T::COLUMNS,
+        gl_type: T::GL_TYPE,
+        kind: T::KIND,
+        offset,
+    }
+}
+
+// Implements `VertexLayout` for a `#[repr(C)]` struct; every
+// listed field becomes an attribute whose name must match
+// the corresponding vertex shader input
+#[macro_export]
+macro_rules! impl_vertex_layout {
+    ($vertex:ty { $($field:ident),+ $(,)? }) => {
+        impl $crate::VertexLayout for $vertex {
+            fn attributes() -> Vec<$crate::VertexAttribute> {
+                let vertex = ::std::mem::MaybeUninit::<$vertex>::uninit();
+                let base = vertex.as_ptr();
+                vec![
+                    $({
+                        // Only computes the field address, the
+                        // uninitialized memory is never read
+                        let field = unsafe { ::std::ptr::addr_of!((*base).$field) };
+                        let offset = field as usize - base as usize;
+                        $crate::vertex::attribute_for(field, stringify!($field), offset)
+                    }),+
+                ]
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Instance {
+        transform: [[f32; 4]; 4],
+        precise: [[f64; 3]; 3],
+        uv: [[f64; 2]; 2],
+    }
+    crate::impl_vertex_layout!(Instance { transform, precise, uv });
+
+    fn locations(attrib: &VertexAttribute, location: types::GLuint) -> Vec<types::GLuint> {
+        (0..attrib.columns).map(|column| attrib.column_location(location, column)).collect()
+    }
+
+    #[test]
+    fn float_matrix_columns_take_one_location() {
+        let attributes = Instance::attributes();
+        assert_eq!(locations(&attributes[0], 0), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn dmat3_columns_take_two_locations() {
+        let attributes = Instance::attributes();
+        assert_eq!(locations(&attributes[1], 4), [4, 6, 8]);
+    }
+
+    #[test]
+    fn dmat2_columns_take_one_location() {
+        let attributes = Instance::attributes();
+        assert_eq!(locations(&attributes[2], 10), [10, 11]);
+    }
+}